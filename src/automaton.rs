@@ -0,0 +1,210 @@
+use std::fmt;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::modules::Module;
+use crate::trigger::Trigger;
+
+/// Maximum number of cells, the packed value has one bit per cell
+pub const MAX_WIDTH: usize = 64;
+
+/// Initial state of an automaton
+#[derive(Debug, Clone, PartialEq)]
+pub enum Seed {
+    /// Only the center cell is alive
+    Center,
+    /// Each cell is alive with a probability of 0.5,
+    /// the same seed value gives the same pattern
+    Random(u64),
+    /// Bit `i` is the state of cell `i`
+    Bits(u64),
+}
+
+/// Elementary (one dimensional, two state) cellular automaton
+/// on a ring of cells, using Wolfram's rule numbering
+#[derive(Debug, Clone)]
+pub struct Cellular {
+    cells: Vec<bool>,
+    next: Vec<bool>,
+    rule: u8,
+}
+
+impl Cellular {
+    pub fn new(width: usize, rule: u8, seed: &Seed) -> Self {
+        assert!((1..=MAX_WIDTH).contains(&width), "Automaton width must be 1 to 64 cells");
+        let mut cellular = Self {
+            cells: vec![false; width],
+            next: vec![false; width],
+            rule,
+        };
+
+        cellular.seed(seed);
+        cellular
+    }
+
+    pub fn seed(&mut self, seed: &Seed) {
+        let width = self.cells.len();
+        match seed {
+            Seed::Center => {
+                for (i, cell) in self.cells.iter_mut().enumerate() {
+                    *cell = i == width / 2;
+                }
+            }
+            Seed::Random(seed) => {
+                let mut rng = StdRng::seed_from_u64(*seed);
+                for cell in self.cells.iter_mut() {
+                    *cell = rng.gen();
+                }
+            }
+            Seed::Bits(bits) => {
+                for (i, cell) in self.cells.iter_mut().enumerate() {
+                    *cell = bits & (1 << i) != 0;
+                }
+            }
+        }
+    }
+
+    pub fn set_rule(&mut self, rule: u8) {
+        self.rule = rule;
+    }
+
+    pub fn rule(&self) -> u8 {
+        self.rule
+    }
+
+    pub fn width(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn cells(&self) -> &[bool] {
+        &self.cells
+    }
+
+    /// Advance by one generation
+    pub fn step(&mut self) {
+        let width = self.cells.len();
+
+        for i in 0..width {
+            let left = self.cells[(i + width - 1) % width];
+            let right = self.cells[(i + 1) % width];
+
+            let code = (left as u8) << 2 | (self.cells[i] as u8) << 1 | right as u8;
+            self.next[i] = (self.rule & (1 << code)) != 0;
+        }
+
+        std::mem::swap(&mut self.cells, &mut self.next);
+    }
+
+    /// Fraction of living cells, 0..1
+    pub fn density(&self) -> f64 {
+        self.cells.iter().filter(|c| **c).count() as f64 / self.cells.len() as f64
+    }
+
+    /// Cell states as bits, cell 0 is the least significant bit
+    pub fn packed(&self) -> u64 {
+        self.cells.iter().enumerate()
+            .filter(|(_, c)| **c)
+            .fold(0, |v, (i, _)| v | 1 << i)
+    }
+
+    /// Packed value scaled to 0..1
+    pub fn value(&self) -> f64 {
+        let max = if self.cells.len() == MAX_WIDTH {
+            u64::MAX
+        } else {
+            (1 << self.cells.len()) - 1
+        };
+        self.packed() as f64 / max as f64
+    }
+}
+
+impl fmt::Display for Cellular {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for cell in self.cells.iter() {
+            write!(f, "{}", if *cell { '#' } else { '_' })?;
+        }
+        Ok(())
+    }
+}
+
+/// Elementary cellular automaton advancing one generation per clock.
+///
+/// Cell gates follow the clock,
+/// so living cells can drive envelopes directly.
+///
+/// Inputs:
+///
+/// 0. Clock
+/// 1. Rule, 0..255
+/// 2. Reset, restores the initial seed
+///
+/// Outputs:
+///
+/// 0. Packed value, 0..1
+/// 1. Density, the fraction of living cells
+/// 2. Gate of cell 0, followed by the gates of all other cells
+pub struct Automaton {
+    cellular: Cellular,
+    seed: Seed,
+    clock: Trigger,
+    reset: Trigger,
+}
+
+impl Automaton {
+    pub fn new(width: usize, rule: u8, seed: Seed) -> Self {
+        Self {
+            cellular: Cellular::new(width, rule, &seed),
+            seed,
+            clock: Trigger::new(),
+            reset: Trigger::new(),
+        }
+    }
+
+    pub fn cellular(&mut self) -> &mut Cellular {
+        &mut self.cellular
+    }
+
+    pub fn reset(&mut self) {
+        self.cellular.seed(&self.seed);
+    }
+}
+
+impl Module for Automaton {
+    fn get(&mut self) -> f64 {
+        self.cellular.value()
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 if self.clock.process(val) => self.cellular.step(),
+            1 => self.cellular.set_rule(val.round().clamp(0.0, 255.0) as u8),
+            2 if self.reset.process(val) => self.reset(),
+            _ => ()
+        }
+    }
+
+    fn outputs(&self) -> usize {
+        2 + self.cellular.width()
+    }
+
+    fn get_output(&self, i: usize) -> f64 {
+        match i {
+            0 => self.cellular.value(),
+            1 => self.cellular.density(),
+            _ => {
+                let alive = self.cellular.cells().get(i - 2).cloned().unwrap_or(false);
+                if alive && self.clock.is_high() { 1.0 } else { 0.0 }
+            }
+        }
+    }
+
+    /// Replaces a `Seed::Random` seed and starts over from the new pattern,
+    /// other seeds are kept
+    fn set_seed(&mut self, seed: u64) {
+        if let Seed::Random(_) = self.seed {
+            self.seed = Seed::Random(seed);
+            self.reset();
+        }
+    }
+}
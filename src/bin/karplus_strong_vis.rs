@@ -1,6 +1,8 @@
 use patchwork::freeverb::Freeverb;
+use patchwork::distortion::Curve;
+use patchwork::random::RngSource;
 
-use rand::{Rng, thread_rng};
+use rand::Rng;
 
 pub struct KarplusStrong {
     pub wavetable: Vec<f64>,
@@ -9,11 +11,10 @@ pub struct KarplusStrong {
 }
 
 impl KarplusStrong {
-    pub fn new(freq: f64, sample_rate: u32) -> Self {
+    pub fn new<R: Rng>(freq: f64, sample_rate: u32, rng: &mut R) -> Self {
         let p = (f64::from(sample_rate) / freq + 0.5) as usize;
 
         let mut wavetable = Vec::with_capacity(p);
-        let mut rng = thread_rng();
         for _i in 0..p {
             // wavetable.push(rng.gen_range(-1.0, 1.0));
             if rng.gen_range(0.0, 1.0) > 0.5 {
@@ -62,7 +63,7 @@ impl KarplusStrong {
 fn main() {
     let freq = 110.0;
     let sample_rate = 48_000;
-    let mut ks = KarplusStrong::new(freq, sample_rate);
+    let mut ks = KarplusStrong::new(freq, sample_rate, &mut RngSource::from_args().rng());
 
     let height = 48000;
     let width = ks.wavetable.len();
@@ -94,17 +95,9 @@ fn main() {
             // let (l, r) = freeverb.process((v, v));
             let (l, r) = (v, v);
 
-            // // Distortion effect
-            let l = if l > 0.0 {
-               1.0 - f64::exp(-l)
-            } else {
-               -1.0 + f64::exp(l)
-            };
-            let r = if r > 0.0 {
-               1.0 - f64::exp(-r)
-            } else {
-               -1.0 + f64::exp(r)
-            };
+            // Distortion effect
+            let l = Curve::Exponential.apply(l);
+            let r = Curve::Exponential.apply(r);
             let l = (l * std::i16::MAX as f64) as i32;
             let r = (r * std::i16::MAX as f64) as i32;
             writer.write_sample(l).unwrap();
@@ -0,0 +1,110 @@
+//! Renders a Game of Life to `life.wav`.
+//!
+//! With `--pgm`, every generation is written to stdout
+//! as one image, generations stacked from top to bottom.
+//! `--seed <n>` changes the random starting grid.
+
+use std::io::{self, Write};
+
+use patchwork::SAMPLE_RATE;
+use patchwork::clock::Clock;
+use patchwork::life::{Life, LifeModule, Rule};
+use patchwork::modules::{Module, Sine};
+use patchwork::pitch::{Scale, midi_to_freq, parse_note};
+use patchwork::random::RngSource;
+use patchwork::trigger::Trigger;
+use patchwork::util::clamp;
+
+const WIDTH: usize = 16;
+const HEIGHT: usize = 10;
+const SECONDS: usize = 30;
+
+/// Sine with a decaying envelope, started by a gate
+struct Voice {
+    osc: Sine,
+    trigger: Trigger,
+    envelope: f64,
+    decay: f64,
+}
+
+impl Voice {
+    fn new(freq: f64, decay: f64) -> Self {
+        Self {
+            osc: Sine::new(freq),
+            trigger: Trigger::new(),
+            envelope: 0.0,
+            decay: (-1.0 / (decay * SAMPLE_RATE)).exp(),
+        }
+    }
+
+    fn process(&mut self, gate: f64) -> f64 {
+        if self.trigger.process(gate) {
+            self.envelope = 1.0;
+        }
+        self.envelope *= self.decay;
+        self.osc.get() * self.envelope
+    }
+}
+
+fn main() {
+    let pgm = std::env::args().any(|a| a == "--pgm");
+
+    let mut life = Life::new(WIDTH, HEIGHT, Rule::life());
+    life.randomize(RngSource::from_args().next_seed(), 0.25);
+    life.insert(1, 1, &[".#.", "..#", "###"]);
+
+    // Major pentatonic from A2
+    let root = parse_note("A2").unwrap();
+    let scale = (0..5)
+        .map(|degree| midi_to_freq(Scale::MajorPentatonic.note(root, degree) as f64))
+        .collect();
+    let mut module = LifeModule::new(life, scale);
+    let mut clock = Clock::new(120.0).with_resolution(4);
+
+    let mut voices: Vec<Voice> = (0..HEIGHT).map(|y| Voice::new(module.row_freq(y), 0.3)).collect();
+    voices.reverse();
+    let mut click = Voice::new(2000.0, 0.05);
+
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE as u32,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create("life.wav", spec).unwrap();
+
+    // Pixel rows of all generations, the header needs the total height
+    let mut frames = Vec::new();
+    let mut generations = 0;
+    let mut generation = None;
+
+    for _ in 0..(SECONDS * SAMPLE_RATE as usize) {
+        module.set_input(0, clock.get());
+        let amplitude = module.get();
+
+        if pgm && generation != Some(module.life().generation()) {
+            generation = Some(module.life().generation());
+            generations += 1;
+            module.life().write_pgm_rows(&mut frames).unwrap();
+        }
+
+        let mut sample = 0.0;
+        for (i, voice) in voices.iter_mut().enumerate() {
+            sample += voice.process(module.get_output(4 + i));
+        }
+        sample *= 0.5 + amplitude;
+        sample += click.process(module.get_output(2)) * 0.3;
+
+        let sample = clamp(sample / HEIGHT as f64, -1.0, 1.0);
+        writer.write_sample((sample * i16::MAX as f64) as i16).unwrap();
+    }
+
+    if pgm {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        writeln!(out, "P2").unwrap();
+        writeln!(out, "{} {}", WIDTH, HEIGHT * generations).unwrap();
+        writeln!(out, "255").unwrap();
+        out.write_all(&frames).unwrap();
+    }
+}
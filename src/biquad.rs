@@ -0,0 +1,448 @@
+use crate::SAMPLE_RATE;
+use crate::modules::Module;
+use crate::util::clamp;
+
+const PI: f64 = std::f64::consts::PI;
+const TWOPI: f64 = std::f64::consts::PI * 2.0;
+
+/// Filter shapes from Robert Bristow-Johnson's
+/// "Cookbook formulae for audio EQ biquad filter coefficients".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BiquadKind {
+    Lowpass,
+    Highpass,
+    /// Constant 0 dB peak gain
+    Bandpass,
+    Notch,
+    Allpass,
+    Peaking,
+    LowShelf,
+    HighShelf,
+}
+
+/// Lowpass or highpass side of a higher order cascade
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    Lowpass,
+    Highpass,
+}
+
+/// Coefficients of a biquad, normalized so that `a0 = 1`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coefficients {
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+}
+
+impl Coefficients {
+    /// `gain` is in dB and only used by the peaking and shelving filters.
+    pub fn new(kind: BiquadKind, freq: f64, q: f64, gain: f64) -> Self {
+        let freq = clamp(freq, 1.0, SAMPLE_RATE * 0.49);
+        let q = q.max(0.01);
+
+        let w0 = TWOPI * freq / SAMPLE_RATE;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let a = 10.0_f64.powf(gain / 40.0);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+
+        // b0, b1, b2, a0, a1, a2
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            BiquadKind::Lowpass => (
+                (1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0,
+                1.0 + alpha, -2.0 * cos, 1.0 - alpha,
+            ),
+            BiquadKind::Highpass => (
+                (1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0,
+                1.0 + alpha, -2.0 * cos, 1.0 - alpha,
+            ),
+            BiquadKind::Bandpass => (
+                alpha, 0.0, -alpha,
+                1.0 + alpha, -2.0 * cos, 1.0 - alpha,
+            ),
+            BiquadKind::Notch => (
+                1.0, -2.0 * cos, 1.0,
+                1.0 + alpha, -2.0 * cos, 1.0 - alpha,
+            ),
+            BiquadKind::Allpass => (
+                1.0 - alpha, -2.0 * cos, 1.0 + alpha,
+                1.0 + alpha, -2.0 * cos, 1.0 - alpha,
+            ),
+            BiquadKind::Peaking => (
+                1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a,
+                1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a,
+            ),
+            BiquadKind::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos + sqrt_a_alpha),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - sqrt_a_alpha),
+                (a + 1.0) + (a - 1.0) * cos + sqrt_a_alpha,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - sqrt_a_alpha,
+            ),
+            BiquadKind::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos + sqrt_a_alpha),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - sqrt_a_alpha),
+                (a + 1.0) - (a - 1.0) * cos + sqrt_a_alpha,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - sqrt_a_alpha,
+            ),
+        };
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    /// First order section (bilinear transform of a one-pole),
+    /// used for odd order butterworth filters
+    pub fn first_order(pass: Pass, freq: f64) -> Self {
+        let freq = clamp(freq, 1.0, SAMPLE_RATE * 0.49);
+        let k = (PI * freq / SAMPLE_RATE).tan();
+        let a1 = (k - 1.0) / (k + 1.0);
+
+        match pass {
+            Pass::Lowpass => {
+                let b0 = k / (1.0 + k);
+                Self { b0, b1: b0, b2: 0.0, a1, a2: 0.0 }
+            }
+            Pass::Highpass => {
+                let b0 = 1.0 / (1.0 + k);
+                Self { b0, b1: -b0, b2: 0.0, a1, a2: 0.0 }
+            }
+        }
+    }
+
+    /// Complex response `(re, im)` at `freq` Hz
+    fn complex_response(&self, freq: f64) -> (f64, f64) {
+        let w = TWOPI * freq / SAMPLE_RATE;
+        // z^-1 and z^-2 on the unit circle
+        let (s1, c1) = (-w).sin_cos();
+        let (s2, c2) = (-2.0 * w).sin_cos();
+
+        let num = (self.b0 + self.b1 * c1 + self.b2 * c2, self.b1 * s1 + self.b2 * s2);
+        let den = (1.0 + self.a1 * c1 + self.a2 * c2, self.a1 * s1 + self.a2 * s2);
+
+        let norm = den.0 * den.0 + den.1 * den.1;
+        (
+            (num.0 * den.0 + num.1 * den.1) / norm,
+            (num.1 * den.0 - num.0 * den.1) / norm,
+        )
+    }
+
+    /// Magnitude (linear) and phase (radians) at `freq` Hz
+    pub fn response(&self, freq: f64) -> (f64, f64) {
+        response(&[*self], freq)
+    }
+}
+
+/// Magnitude (linear) and phase (radians, wrapped to -pi..pi)
+/// of a series of biquads at `freq` Hz,
+/// `util::linear_to_db` converts the magnitude to dB
+pub fn response(coefficients: &[Coefficients], freq: f64) -> (f64, f64) {
+    let mut res = (1.0, 0.0);
+    for c in coefficients {
+        let (re, im) = c.complex_response(freq);
+        res = (res.0 * re - res.1 * im, res.0 * im + res.1 * re);
+    }
+
+    ((res.0 * res.0 + res.1 * res.1).sqrt(), res.1.atan2(res.0))
+}
+
+/// Biquad in transposed direct form II
+///
+/// Inputs:
+///
+/// 0. Signal
+/// 1. Frequency in Hz
+/// 2. Q
+/// 3. Gain in dB (peaking and shelving filters only)
+#[derive(Debug, Clone)]
+pub struct Biquad {
+    kind: BiquadKind,
+    freq: f64,
+    q: f64,
+    gain: f64,
+    coefficients: Coefficients,
+    input: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    pub fn new(kind: BiquadKind, freq: f64, q: f64, gain: f64) -> Self {
+        Self {
+            kind,
+            freq,
+            q,
+            gain,
+            coefficients: Coefficients::new(kind, freq, q, gain),
+            input: 0.0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Filter with fixed coefficients,
+    /// changing frequency, Q or gain will overwrite them.
+    pub fn from_coefficients(coefficients: Coefficients) -> Self {
+        Self {
+            kind: BiquadKind::Allpass,
+            freq: 1000.0,
+            q: 0.5_f64.sqrt(),
+            gain: 0.0,
+            coefficients,
+            input: 0.0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn coefficients(&self) -> Coefficients {
+        self.coefficients
+    }
+
+    pub fn set_freq(&mut self, freq: f64) {
+        self.freq = freq;
+        self.update();
+    }
+
+    pub fn set_q(&mut self, q: f64) {
+        self.q = q;
+        self.update();
+    }
+
+    pub fn set_gain(&mut self, gain: f64) {
+        self.gain = gain;
+        self.update();
+    }
+
+    fn update(&mut self) {
+        self.coefficients = Coefficients::new(self.kind, self.freq, self.q, self.gain);
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    pub fn process(&mut self, input: f64) -> f64 {
+        let c = &self.coefficients;
+        let output = c.b0 * input + self.z1;
+        self.z1 = c.b1 * input - c.a1 * output + self.z2;
+        self.z2 = c.b2 * input - c.a2 * output;
+        output
+    }
+}
+
+impl Module for Biquad {
+    fn get(&mut self) -> f64 {
+        self.process(self.input)
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.input = val,
+            1 => self.set_freq(val),
+            2 => self.set_q(val),
+            3 => self.set_gain(val),
+            _ => ()
+        }
+    }
+}
+
+/// Series of biquads for higher order filters
+#[derive(Debug, Clone)]
+pub struct Cascade {
+    stages: Vec<Biquad>,
+    input: f64,
+}
+
+impl Cascade {
+    pub fn new() -> Self {
+        Self { stages: Vec::new(), input: 0.0 }
+    }
+
+    pub fn with(mut self, coefficients: Coefficients) -> Self {
+        self.push(coefficients);
+        self
+    }
+
+    pub fn push(&mut self, coefficients: Coefficients) {
+        self.stages.push(Biquad::from_coefficients(coefficients));
+    }
+
+    /// Butterworth filter of arbitrary order,
+    /// odd orders use an additional first order section.
+    pub fn butterworth(pass: Pass, order: usize, freq: f64) -> Self {
+        assert!(order > 0, "Filter order must be at least 1");
+
+        let mut cascade = Self::new();
+        let n = order as f64;
+        let kind = match pass {
+            Pass::Lowpass => BiquadKind::Lowpass,
+            Pass::Highpass => BiquadKind::Highpass,
+        };
+
+        // Each biquad implements one conjugate pole pair
+        for k in 0..(order / 2) {
+            let angle = PI * (2.0 * k as f64 + 1.0) / (2.0 * n);
+            let q = 1.0 / (2.0 * angle.sin());
+            cascade.push(Coefficients::new(kind, freq, q, 0.0));
+        }
+
+        if order % 2 == 1 {
+            cascade.push(Coefficients::first_order(pass, freq));
+        }
+
+        cascade
+    }
+
+    /// Linkwitz-Riley filter (two cascaded butterworth filters of half the order).
+    /// For orders divisible by 4 the lowpass and highpass outputs sum
+    /// to a flat magnitude response, for orders 2, 6, ... the highpass
+    /// has to be inverted first, otherwise they cancel at `freq`.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn linkwitz_riley(pass: Pass, order: usize, freq: f64) -> Self {
+        assert!(order >= 2 && order % 2 == 0, "Linkwitz-Riley filters need an even order");
+
+        let half = Self::butterworth(pass, order / 2, freq);
+        let mut cascade = half.clone();
+        cascade.stages.extend(half.stages);
+        cascade
+    }
+
+    pub fn coefficients(&self) -> Vec<Coefficients> {
+        self.stages.iter().map(|s| s.coefficients()).collect()
+    }
+
+    /// Magnitude (linear) and phase (radians) at `freq` Hz
+    pub fn response(&self, freq: f64) -> (f64, f64) {
+        response(&self.coefficients(), freq)
+    }
+
+    pub fn reset(&mut self) {
+        for stage in self.stages.iter_mut() {
+            stage.reset();
+        }
+    }
+
+    pub fn process(&mut self, input: f64) -> f64 {
+        self.stages.iter_mut().fold(input, |acc, stage| stage.process(acc))
+    }
+}
+
+impl Default for Cascade {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for Cascade {
+    fn get(&mut self) -> f64 {
+        self.process(self.input)
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        if i == 0 {
+            self.input = val;
+        }
+    }
+}
+
+/// Three band parametric EQ,
+/// low shelf at 100 Hz, a sweepable peak and a high shelf at 8 kHz.
+///
+/// Inputs:
+///
+/// 0. Signal
+/// 1. Low shelf gain in dB
+/// 2. Peak frequency in Hz
+/// 3. Peak gain in dB
+/// 4. Peak Q
+/// 5. High shelf gain in dB
+#[derive(Debug, Clone)]
+pub struct ParametricEq {
+    low: Biquad,
+    mid: Biquad,
+    high: Biquad,
+    input: f64,
+}
+
+impl ParametricEq {
+    pub fn new() -> Self {
+        let q = 0.5_f64.sqrt();
+        Self {
+            low: Biquad::new(BiquadKind::LowShelf, 100.0, q, 0.0),
+            mid: Biquad::new(BiquadKind::Peaking, 1000.0, 1.0, 0.0),
+            high: Biquad::new(BiquadKind::HighShelf, 8000.0, q, 0.0),
+            input: 0.0,
+        }
+    }
+
+    pub fn set_low_gain(&mut self, gain: f64) {
+        self.low.set_gain(gain);
+    }
+
+    pub fn set_mid_freq(&mut self, freq: f64) {
+        self.mid.set_freq(freq);
+    }
+
+    pub fn set_mid_gain(&mut self, gain: f64) {
+        self.mid.set_gain(gain);
+    }
+
+    pub fn set_mid_q(&mut self, q: f64) {
+        self.mid.set_q(q);
+    }
+
+    pub fn set_high_gain(&mut self, gain: f64) {
+        self.high.set_gain(gain);
+    }
+
+    /// Magnitude (linear) and phase (radians) at `freq` Hz
+    pub fn response(&self, freq: f64) -> (f64, f64) {
+        response(
+            &[self.low.coefficients(), self.mid.coefficients(), self.high.coefficients()],
+            freq,
+        )
+    }
+
+    pub fn process(&mut self, input: f64) -> f64 {
+        let low = self.low.process(input);
+        let mid = self.mid.process(low);
+        self.high.process(mid)
+    }
+}
+
+impl Default for ParametricEq {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for ParametricEq {
+    fn get(&mut self) -> f64 {
+        self.process(self.input)
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.input = val,
+            1 => self.set_low_gain(val),
+            2 => self.set_mid_freq(val),
+            3 => self.set_mid_gain(val),
+            4 => self.set_mid_q(val),
+            5 => self.set_high_gain(val),
+            _ => ()
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use crate::SAMPLE_RATE;
+use crate::modules::Module;
+use crate::trigger::{Pulse, Trigger};
+use crate::util::clamp;
+
+/// Maximum swing, keeps the delayed tick before the next one
+const MAX_SWING: f64 = 0.9;
+
+/// Master clock emitting trigger pulses.
+///
+/// With swing, every second tick is delayed
+/// by up to half a tick (`swing` 0..1).
+///
+/// Inputs:
+///
+/// 0. Tempo in BPM
+/// 1. Swing, 0..1
+/// 2. Reset, restarts with a tick on a rising edge
+///
+/// Outputs:
+///
+/// 0. Clock
+/// 1. Beat, one pulse per quarter note
+pub struct Clock {
+    bpm: f64,
+    resolution: usize,
+    swing: f64,
+    // Position within a pair of ticks, 0..2
+    phase: f64,
+    step: f64,
+    // Ticks since the last beat
+    tick: usize,
+    started: bool,
+    reset: Trigger,
+    clock: Pulse,
+    beat: Pulse,
+    beat_value: f64,
+}
+
+impl Clock {
+    /// Clock with one tick per quarter note
+    pub fn new(bpm: f64) -> Self {
+        let mut clock = Self {
+            bpm,
+            resolution: 1,
+            swing: 0.0,
+            phase: 0.0,
+            step: 0.0,
+            tick: 0,
+            started: false,
+            reset: Trigger::new(),
+            clock: Pulse::new(),
+            beat: Pulse::new(),
+            beat_value: 0.0,
+        };
+
+        clock.set_bpm(bpm);
+        clock
+    }
+
+    /// Number of ticks per quarter note, e.g. 4 for sixteenth notes
+    pub fn with_resolution(mut self, resolution: usize) -> Self {
+        self.resolution = resolution.max(1);
+        self.set_bpm(self.bpm);
+        self
+    }
+
+    pub fn with_swing(mut self, swing: f64) -> Self {
+        self.set_swing(swing);
+        self
+    }
+
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.bpm = bpm.max(0.0);
+        self.step = self.bpm / 60.0 * self.resolution as f64 / SAMPLE_RATE;
+    }
+
+    pub fn set_swing(&mut self, swing: f64) {
+        self.swing = clamp(swing, 0.0, MAX_SWING);
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+        self.tick = 0;
+        self.started = false;
+    }
+
+    fn tick(&mut self) {
+        if self.tick == 0 {
+            self.beat.trigger();
+        }
+        self.tick = (self.tick + 1) % self.resolution;
+        self.clock.trigger();
+    }
+}
+
+impl Module for Clock {
+    fn get(&mut self) -> f64 {
+        if !self.started {
+            self.started = true;
+            self.tick();
+        } else {
+            // The second tick of each pair is delayed by the swing
+            let offbeat = 1.0 + self.swing * 0.5;
+            let previous = self.phase;
+            self.phase += self.step;
+
+            if previous < offbeat && self.phase >= offbeat {
+                self.tick();
+            }
+            if self.phase >= 2.0 {
+                self.phase -= 2.0;
+                self.tick();
+            }
+        }
+
+        self.beat_value = self.beat.process();
+        self.clock.process()
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.set_bpm(val),
+            1 => self.set_swing(val),
+            2 if self.reset.process(val) => self.reset(),
+            _ => ()
+        }
+    }
+
+    fn outputs(&self) -> usize {
+        2
+    }
+
+    fn get_output(&self, i: usize) -> f64 {
+        match i {
+            1 => self.beat_value,
+            _ => 0.0
+        }
+    }
+}
+
+/// Emits a trigger on every n-th input trigger,
+/// starting with the first.
+///
+/// Inputs:
+///
+/// 0. Clock
+/// 1. Reset
+/// 2. Division, rounded to an integer
+pub struct ClockDivider {
+    division: usize,
+    count: usize,
+    clock: Trigger,
+    reset: Trigger,
+    pulse: Pulse,
+}
+
+impl ClockDivider {
+    pub fn new(division: usize) -> Self {
+        Self {
+            division: division.max(1),
+            count: 0,
+            clock: Trigger::new(),
+            reset: Trigger::new(),
+            pulse: Pulse::new(),
+        }
+    }
+
+    pub fn set_division(&mut self, division: usize) {
+        self.division = division.max(1);
+    }
+
+    pub fn reset(&mut self) {
+        self.count = 0;
+    }
+
+    fn clock(&mut self) {
+        if self.count == 0 {
+            self.pulse.trigger();
+        }
+        self.count = (self.count + 1) % self.division;
+    }
+}
+
+impl Module for ClockDivider {
+    fn get(&mut self) -> f64 {
+        self.pulse.process()
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 if self.clock.process(val) => self.clock(),
+            1 if self.reset.process(val) => self.reset(),
+            2 => self.set_division(val.round().max(1.0) as usize),
+            _ => ()
+        }
+    }
+}
+
+/// Emits `factor` evenly spaced triggers per input trigger.
+///
+/// The spacing is measured from the last two input triggers,
+/// so the first interval only passes the input through.
+///
+/// Inputs:
+///
+/// 0. Clock
+/// 1. Factor, rounded to an integer
+pub struct ClockMultiplier {
+    factor: usize,
+    clock: Trigger,
+    pulse: Pulse,
+    // Samples between the last two input triggers
+    interval: Option<usize>,
+    // Samples since the last input trigger
+    elapsed: usize,
+    // Triggers emitted since the last input trigger
+    emitted: usize,
+}
+
+impl ClockMultiplier {
+    pub fn new(factor: usize) -> Self {
+        Self {
+            factor: factor.max(1),
+            clock: Trigger::new(),
+            pulse: Pulse::new(),
+            interval: None,
+            elapsed: 0,
+            emitted: 0,
+        }
+    }
+
+    pub fn set_factor(&mut self, factor: usize) {
+        self.factor = factor.max(1);
+    }
+
+    fn clock(&mut self) {
+        if self.emitted > 0 {
+            self.interval = Some(self.elapsed);
+        }
+        self.elapsed = 0;
+        self.emitted = 1;
+        self.pulse.trigger();
+    }
+}
+
+impl Module for ClockMultiplier {
+    fn get(&mut self) -> f64 {
+        self.elapsed += 1;
+
+        if let Some(interval) = self.interval {
+            if self.emitted < self.factor
+                && self.elapsed * self.factor >= interval * self.emitted {
+                self.emitted += 1;
+                self.pulse.trigger();
+            }
+        }
+
+        self.pulse.process()
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 if self.clock.process(val) => self.clock(),
+            1 => self.set_factor(val.round().max(1.0) as usize),
+            _ => ()
+        }
+    }
+}
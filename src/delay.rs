@@ -0,0 +1,414 @@
+use crate::SAMPLE_RATE;
+use crate::modules::Module;
+use crate::util::clamp;
+use crate::tempo::Division;
+
+const TWOPI: f64 = std::f64::consts::PI * 2.0;
+
+/// How to read between two samples of a delay line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    /// First order allpass, flat magnitude response
+    /// but only suited for slowly changing delay times
+    Allpass,
+    /// 4-point cubic hermite
+    Cubic,
+}
+
+/// Circular buffer that can be read at fractional positions
+#[derive(Debug, Clone)]
+pub struct DelayLine {
+    buffer: Vec<f64>,
+    index: usize,
+    interpolation: Interpolation,
+    // Last output of the allpass interpolator
+    allpass_state: f64,
+}
+
+impl DelayLine {
+    /// `length` is the maximum delay in samples
+    pub fn new(length: usize, interpolation: Interpolation) -> Self {
+        Self {
+            buffer: vec![0.0; length + 4],
+            index: 0,
+            interpolation,
+            allpass_state: 0.0,
+        }
+    }
+
+    /// Maximum delay in samples
+    pub fn max_delay(&self) -> f64 {
+        (self.buffer.len() - 4) as f64
+    }
+
+    /// Smallest delay the interpolation method supports
+    pub fn min_delay(&self) -> f64 {
+        match self.interpolation {
+            Interpolation::Linear => 1.0,
+            Interpolation::Allpass => 1.5,
+            Interpolation::Cubic => 2.0,
+        }
+    }
+
+    pub fn write(&mut self, value: f64) {
+        self.buffer[self.index] = value;
+        self.index += 1;
+        if self.index >= self.buffer.len() {
+            self.index = 0;
+        }
+    }
+
+    /// Value written `delay` samples ago
+    pub fn tap(&self, delay: usize) -> f64 {
+        let len = self.buffer.len();
+        self.buffer[(self.index + len - delay) % len]
+    }
+
+    /// Read `delay` samples in the past,
+    /// the delay is clamped to `min_delay()..max_delay()`
+    pub fn read(&mut self, delay: f64) -> f64 {
+        let delay = clamp(delay, self.min_delay(), self.max_delay());
+        let whole = delay.floor();
+        let frac = delay - whole;
+        let whole = whole as usize;
+
+        match self.interpolation {
+            Interpolation::Linear => {
+                let a = self.tap(whole);
+                let b = self.tap(whole + 1);
+                a + (b - a) * frac
+            }
+            Interpolation::Allpass => {
+                // Keep the fractional part in 0.5..1.5,
+                // the allpass coefficient misbehaves close to 0
+                let (whole, frac) = if frac < 0.5 {
+                    (whole - 1, frac + 1.0)
+                } else {
+                    (whole, frac)
+                };
+                let a = (1.0 - frac) / (1.0 + frac);
+                let output = a * self.tap(whole) + self.tap(whole + 1) - a * self.allpass_state;
+                self.allpass_state = output;
+                output
+            }
+            Interpolation::Cubic => {
+                let x0 = self.tap(whole - 1);
+                let x1 = self.tap(whole);
+                let x2 = self.tap(whole + 1);
+                let x3 = self.tap(whole + 2);
+
+                let c1 = 0.5 * (x2 - x0);
+                let c2 = x0 - 2.5 * x1 + 2.0 * x2 - 0.5 * x3;
+                let c3 = 0.5 * (x3 - x0) + 1.5 * (x1 - x2);
+                ((c3 * frac + c2) * frac + c1) * frac + x1
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for v in self.buffer.iter_mut() {
+            *v = 0.0;
+        }
+        self.allpass_state = 0.0;
+    }
+}
+
+/// First order allpass delaying low frequencies by a fractional amount,
+/// used to fine tune the length of feedback loops
+#[derive(Debug, Clone)]
+pub struct FractionalAllpass {
+    coefficient: f64,
+    input: f64,
+    output: f64,
+}
+
+impl FractionalAllpass {
+    pub fn new(delay: f64) -> Self {
+        let mut allpass = Self {
+            coefficient: 0.0,
+            input: 0.0,
+            output: 0.0,
+        };
+
+        allpass.set_delay(delay);
+        allpass
+    }
+
+    /// Delay in samples, clamped to 0.5..1.5 where the approximation holds
+    pub fn set_delay(&mut self, delay: f64) {
+        let delay = clamp(delay, 0.5, 1.5);
+        self.coefficient = (1.0 - delay) / (1.0 + delay);
+    }
+
+    pub fn process(&mut self, input: f64) -> f64 {
+        self.output = self.coefficient * (input - self.output) + self.input;
+        self.input = input;
+        self.output
+    }
+
+    pub fn reset(&mut self) {
+        self.input = 0.0;
+        self.output = 0.0;
+    }
+}
+
+/// Delay with a modulatable, fractional delay time.
+///
+/// The feedback path runs through a one-pole lowpass ("tone")
+/// so repeats get darker, like on a tape or analog echo.
+/// Modulating the delay time gives chorus, flanger and vibrato effects.
+///
+/// Inputs:
+///
+/// 0. Signal
+/// 1. Delay time in seconds
+/// 2. Feedback, -1..1
+/// 3. Tone, cutoff of the feedback lowpass in Hz
+/// 4. Mix, 0 = dry, 1 = wet
+pub struct Delay {
+    line: DelayLine,
+    input: f64,
+    delay: f64,
+    feedback: f64,
+    tone: f64,
+    tone_state: f64,
+    mix: f64,
+}
+
+impl Delay {
+    /// `max_length` is the longest supported delay time in seconds
+    pub fn new(max_length: f64, interpolation: Interpolation) -> Self {
+        let slots = (max_length * SAMPLE_RATE).ceil() as usize;
+        let mut delay = Self {
+            line: DelayLine::new(slots, interpolation),
+            input: 0.0,
+            delay: 0.0,
+            feedback: 0.0,
+            tone: 0.0,
+            tone_state: 0.0,
+            mix: 0.5,
+        };
+
+        delay.set_delay(max_length * 0.5);
+        delay.set_tone(SAMPLE_RATE * 0.5);
+        delay
+    }
+
+    /// Delay time in seconds
+    pub fn set_delay(&mut self, delay: f64) {
+        self.delay = delay * SAMPLE_RATE;
+    }
+
+    pub fn set_feedback(&mut self, feedback: f64) {
+        self.feedback = clamp(feedback, -0.99, 0.99);
+    }
+
+    /// Cutoff of the lowpass in the feedback loop, in Hz
+    pub fn set_tone(&mut self, cutoff: f64) {
+        let cutoff = clamp(cutoff, 1.0, SAMPLE_RATE * 0.5);
+        self.tone = (-TWOPI * cutoff / SAMPLE_RATE).exp();
+    }
+
+    pub fn set_mix(&mut self, mix: f64) {
+        self.mix = clamp(mix, 0.0, 1.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.line.reset();
+        self.tone_state = 0.0;
+    }
+
+    pub fn process(&mut self, input: f64) -> f64 {
+        let wet = self.line.read(self.delay);
+
+        self.tone_state = wet * (1.0 - self.tone) + self.tone_state * self.tone;
+        self.line.write(input + self.tone_state * self.feedback);
+
+        input * (1.0 - self.mix) + wet * self.mix
+    }
+}
+
+impl Module for Delay {
+    fn get(&mut self) -> f64 {
+        self.process(self.input)
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.input = val,
+            1 => self.set_delay(val),
+            2 => self.set_feedback(val),
+            3 => self.set_tone(val),
+            4 => self.set_mix(val),
+            _ => ()
+        }
+    }
+}
+
+/// Longest delay time of the `StereoDelay` in seconds
+const MAX_STEREO_DELAY: f64 = 8.0;
+/// Time constant of the delay time smoothing in seconds
+const SMOOTHING_TIME: f64 = 0.1;
+
+/// Tempo synced stereo delay with ping-pong crossfeed.
+///
+/// Changes to the tempo or division glide to the new delay time
+/// instead of jumping, which bends the pitch of the repeats
+/// like on a tape echo but never clicks.
+///
+/// Inputs:
+///
+/// 0. Left signal
+/// 1. Right signal
+/// 2. Tempo in BPM
+/// 3. Division, control value 0..1 selecting one of `tempo::DIVISIONS`
+/// 4. Feedback, 0..1
+/// 5. Crossfeed, 0 = separate channels, 1 = ping-pong starting on the left
+/// 6. Tone, cutoff of the feedback lowpass in Hz
+/// 7. Mix, 0 = dry, 1 = wet
+///
+/// Outputs:
+///
+/// 0. Left
+/// 1. Right
+pub struct StereoDelay {
+    lines: (DelayLine, DelayLine),
+    tone_states: (f64, f64),
+    input: (f64, f64),
+    output: (f64, f64),
+    bpm: f64,
+    division: Division,
+    // Target and current delay time in samples
+    target: f64,
+    delay: f64,
+    smoothing: f64,
+    feedback: f64,
+    crossfeed: f64,
+    tone: f64,
+    mix: f64,
+}
+
+impl StereoDelay {
+    pub fn new(bpm: f64, division: Division) -> Self {
+        let slots = (MAX_STEREO_DELAY * SAMPLE_RATE) as usize;
+        let mut delay = Self {
+            lines: (
+                DelayLine::new(slots, Interpolation::Linear),
+                DelayLine::new(slots, Interpolation::Linear),
+            ),
+            tone_states: (0.0, 0.0),
+            input: (0.0, 0.0),
+            output: (0.0, 0.0),
+            bpm,
+            division,
+            target: 0.0,
+            delay: 0.0,
+            smoothing: (-1.0 / (SMOOTHING_TIME * SAMPLE_RATE)).exp(),
+            feedback: 0.4,
+            crossfeed: 1.0,
+            tone: 0.0,
+            mix: 0.5,
+        };
+
+        delay.update_target();
+        delay.delay = delay.target;
+        delay.set_tone(SAMPLE_RATE * 0.5);
+        delay
+    }
+
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.bpm = bpm;
+        self.update_target();
+    }
+
+    pub fn set_division(&mut self, division: Division) {
+        self.division = division;
+        self.update_target();
+    }
+
+    fn update_target(&mut self) {
+        let seconds = clamp(self.division.seconds(self.bpm), 0.0, MAX_STEREO_DELAY);
+        self.target = seconds * SAMPLE_RATE;
+    }
+
+    pub fn set_feedback(&mut self, feedback: f64) {
+        self.feedback = clamp(feedback, 0.0, 0.99);
+    }
+
+    pub fn set_crossfeed(&mut self, crossfeed: f64) {
+        self.crossfeed = clamp(crossfeed, 0.0, 1.0);
+    }
+
+    /// Cutoff of the lowpass in the feedback loop, in Hz
+    pub fn set_tone(&mut self, cutoff: f64) {
+        let cutoff = clamp(cutoff, 1.0, SAMPLE_RATE * 0.5);
+        self.tone = (-TWOPI * cutoff / SAMPLE_RATE).exp();
+    }
+
+    pub fn set_mix(&mut self, mix: f64) {
+        self.mix = clamp(mix, 0.0, 1.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.lines.0.reset();
+        self.lines.1.reset();
+        self.tone_states = (0.0, 0.0);
+    }
+
+    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        self.delay = self.target + (self.delay - self.target) * self.smoothing;
+
+        let wet = (self.lines.0.read(self.delay), self.lines.1.read(self.delay));
+
+        self.tone_states = (
+            wet.0 * (1.0 - self.tone) + self.tone_states.0 * self.tone,
+            wet.1 * (1.0 - self.tone) + self.tone_states.1 * self.tone,
+        );
+        let (l, r) = self.tone_states;
+        let c = self.crossfeed;
+        // With crossfeed the right input moves into the left line,
+        // at full crossfeed the left line gets the mid of both inputs,
+        // so even a mono source bounces from side to side
+        let left = input.0 * (1.0 - c * 0.5) + input.1 * c * 0.5;
+        self.lines.0.write(left + self.feedback * (l * (1.0 - c) + r * c));
+        self.lines.1.write(input.1 * (1.0 - c) + self.feedback * (r * (1.0 - c) + l * c));
+
+        (
+            input.0 * (1.0 - self.mix) + wet.0 * self.mix,
+            input.1 * (1.0 - self.mix) + wet.1 * self.mix,
+        )
+    }
+}
+
+impl Module for StereoDelay {
+    fn get(&mut self) -> f64 {
+        self.output = self.process(self.input);
+        self.output.0
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.input.0 = val,
+            1 => self.input.1 = val,
+            2 => self.set_bpm(val),
+            3 => self.set_division(Division::from_control(val)),
+            4 => self.set_feedback(val),
+            5 => self.set_crossfeed(val),
+            6 => self.set_tone(val),
+            7 => self.set_mix(val),
+            _ => ()
+        }
+    }
+
+    fn outputs(&self) -> usize {
+        2
+    }
+
+    fn get_output(&self, i: usize) -> f64 {
+        match i {
+            1 => self.output.1,
+            _ => 0.0
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::modules::Module;
+use crate::oversample::Oversampled;
+use crate::util::clamp;
+
+const PI: f64 = std::f64::consts::PI;
+/// Bias of the tube curve, shifts the operating point
+/// so positive and negative half-waves clip differently
+const TUBE_BIAS: f64 = 0.3;
+
+/// Transfer functions for waveshaping,
+/// all are (close to) linear with unity gain around 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    Tanh,
+    /// `1 - exp(-x)`, mirrored for negative inputs
+    Exponential,
+    HardClip,
+    /// Cubic soft clipper, `x - x^3 / 3` scaled to reach 1
+    Cubic,
+    /// Asymmetric, adds even harmonics
+    Tube,
+    /// Reflects the signal back at -1 and 1
+    Foldback,
+    /// `sin(x * pi / 2)`, folds smoothly
+    SineFold,
+}
+
+impl Curve {
+    pub fn apply(&self, x: f64) -> f64 {
+        match self {
+            Curve::Tanh => x.tanh(),
+            Curve::Exponential => {
+                if x > 0.0 {
+                    1.0 - f64::exp(-x)
+                } else {
+                    -1.0 + f64::exp(x)
+                }
+            }
+            Curve::HardClip => clamp(x, -1.0, 1.0),
+            Curve::Cubic => {
+                let x = clamp(x / 1.5, -1.0, 1.0);
+                1.5 * (x - x * x * x / 3.0)
+            }
+            Curve::Tube => (x + TUBE_BIAS).tanh() - TUBE_BIAS.tanh(),
+            Curve::Foldback => 1.0 - (((x + 1.0).rem_euclid(4.0)) - 2.0).abs(),
+            Curve::SineFold => (x * PI * 0.5).sin(),
+        }
+    }
+}
+
+/// Applies a curve to input 0, run by `Oversampled` inside the `Waveshaper`
+struct Shaper {
+    curve: Curve,
+    input: f64,
+}
+
+impl Module for Shaper {
+    fn get(&mut self) -> f64 {
+        self.curve.apply(self.input)
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        if i == 0 {
+            self.input = val;
+        }
+    }
+}
+
+/// Waveshaping distortion with optional oversampling
+/// to reduce aliasing at high drive settings.
+///
+/// Inputs:
+///
+/// 0. Signal
+/// 1. Drive, gain applied before the curve
+/// 2. Output level
+pub struct Waveshaper {
+    input: f64,
+    drive: f64,
+    level: f64,
+    shaper: Oversampled<Shaper>,
+}
+
+impl Waveshaper {
+    pub fn new(curve: Curve) -> Self {
+        Self {
+            input: 0.0,
+            drive: 1.0,
+            level: 1.0,
+            shaper: Oversampled::new(Shaper { curve, input: 0.0 }, 1),
+        }
+    }
+
+    /// Run the curve at `factor` times the sample rate,
+    /// a factor of 1 disables oversampling
+    pub fn with_oversampling(mut self, factor: usize) -> Self {
+        let curve = self.shaper.module().curve;
+        self.shaper = Oversampled::new(Shaper { curve, input: 0.0 }, factor.max(1));
+        self
+    }
+
+    pub fn set_curve(&mut self, curve: Curve) {
+        self.shaper.module().curve = curve;
+    }
+
+    pub fn set_drive(&mut self, drive: f64) {
+        self.drive = drive.max(0.0);
+    }
+
+    pub fn set_level(&mut self, level: f64) {
+        self.level = level;
+    }
+
+    pub fn process(&mut self, input: f64) -> f64 {
+        self.shaper.set_input(0, input * self.drive);
+        self.shaper.get() * self.level
+    }
+}
+
+impl Module for Waveshaper {
+    fn get(&mut self) -> f64 {
+        self.process(self.input)
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.input = val,
+            1 => self.set_drive(val),
+            2 => self.set_level(val),
+            _ => ()
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::VecDeque;
+
+use crate::SAMPLE_RATE;
+use crate::modules::Module;
+use crate::util::{db_to_linear, linear_to_db};
+
+/// Coefficient of a one-pole smoother reaching ~63% after `time` seconds
+fn time_constant(time: f64) -> f64 {
+    if time <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / (time * SAMPLE_RATE)).exp()
+    }
+}
+
+/// Feed-forward compressor with soft knee.
+///
+/// The gain reduction is computed in the log domain
+/// and smoothed with separate attack and release times.
+/// Once the sidechain input has been patched,
+/// the sidechain instead of the signal drives the gain reduction.
+///
+/// Inputs:
+///
+/// 0. Signal
+/// 1. Sidechain
+/// 2. Threshold in dB
+/// 3. Ratio, e.g. 4 for 4:1
+/// 4. Attack time in seconds
+/// 5. Release time in seconds
+/// 6. Knee width in dB
+/// 7. Makeup gain in dB
+///
+/// Outputs:
+///
+/// 0. Signal
+/// 1. Gain reduction in dB
+pub struct Compressor {
+    input: f64,
+    sidechain: Option<f64>,
+    threshold: f64,
+    ratio: f64,
+    attack: f64,
+    release: f64,
+    knee: f64,
+    makeup: f64,
+    // Smoothed gain reduction in dB, >= 0
+    reduction: f64,
+}
+
+impl Compressor {
+    pub fn new(threshold: f64, ratio: f64) -> Self {
+        let mut compressor = Self {
+            input: 0.0,
+            sidechain: None,
+            threshold,
+            ratio: 1.0,
+            attack: 0.0,
+            release: 0.0,
+            knee: 6.0,
+            makeup: 0.0,
+            reduction: 0.0,
+        };
+
+        compressor.set_ratio(ratio);
+        compressor.set_attack(0.01);
+        compressor.set_release(0.1);
+        compressor
+    }
+
+    pub fn set_threshold(&mut self, threshold: f64) {
+        self.threshold = threshold;
+    }
+
+    pub fn set_ratio(&mut self, ratio: f64) {
+        self.ratio = ratio.max(1.0);
+    }
+
+    /// Attack time in seconds
+    pub fn set_attack(&mut self, attack: f64) {
+        self.attack = time_constant(attack);
+    }
+
+    /// Release time in seconds
+    pub fn set_release(&mut self, release: f64) {
+        self.release = time_constant(release);
+    }
+
+    /// Knee width in dB, 0 for a hard knee
+    pub fn set_knee(&mut self, knee: f64) {
+        self.knee = knee.max(0.0);
+    }
+
+    /// Makeup gain in dB
+    pub fn set_makeup(&mut self, makeup: f64) {
+        self.makeup = makeup;
+    }
+
+    /// Current gain reduction in dB
+    pub fn gain_reduction(&self) -> f64 {
+        self.reduction
+    }
+
+    /// Static curve, gain reduction in dB for an input level in dB
+    fn static_reduction(&self, level: f64) -> f64 {
+        let slope = 1.0 - 1.0 / self.ratio;
+        let over = level - self.threshold;
+
+        if self.knee <= 0.0 {
+            slope * over.max(0.0)
+        } else if 2.0 * over < -self.knee {
+            0.0
+        } else if 2.0 * over.abs() <= self.knee {
+            let x = over + self.knee / 2.0;
+            slope * x * x / (2.0 * self.knee)
+        } else {
+            slope * over
+        }
+    }
+
+    /// Update the gain reduction for the detector signal `key`
+    /// and return the linear gain to apply
+    pub fn gain(&mut self, key: f64) -> f64 {
+        let level = linear_to_db(key.abs().max(1e-9));
+        let target = self.static_reduction(level);
+
+        let coefficient = if target > self.reduction {
+            self.attack
+        } else {
+            self.release
+        };
+        self.reduction = target + (self.reduction - target) * coefficient;
+
+        db_to_linear(self.makeup - self.reduction)
+    }
+
+    pub fn process(&mut self, input: f64, key: f64) -> f64 {
+        input * self.gain(key)
+    }
+}
+
+impl Module for Compressor {
+    fn get(&mut self) -> f64 {
+        let key = self.sidechain.unwrap_or(self.input);
+        self.process(self.input, key)
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.input = val,
+            1 => self.sidechain = Some(val),
+            2 => self.set_threshold(val),
+            3 => self.set_ratio(val),
+            4 => self.set_attack(val),
+            5 => self.set_release(val),
+            6 => self.set_knee(val),
+            7 => self.set_makeup(val),
+            _ => ()
+        }
+    }
+
+    fn outputs(&self) -> usize {
+        2
+    }
+
+    fn get_output(&self, i: usize) -> f64 {
+        match i {
+            1 => self.gain_reduction(),
+            _ => 0.0
+        }
+    }
+}
+
+/// Stereo lookahead brickwall limiter.
+///
+/// The signal is delayed by the lookahead time,
+/// so the gain can ramp down before a peak arrives
+/// and the output never exceeds the ceiling.
+pub struct Limiter {
+    ceiling: f64,
+    lookahead: usize,
+    release: f64,
+    // Delayed input
+    delay: VecDeque<(f64, f64)>,
+    // Sliding minimum of the required gain, (sample index, gain)
+    minimum: VecDeque<(usize, f64)>,
+    // Gain after release smoothing, averaged over the lookahead window
+    window: VecDeque<f64>,
+    window_sum: f64,
+    envelope: f64,
+    gain: f64,
+    index: usize,
+}
+
+impl Limiter {
+    /// `ceiling` in dB, `lookahead` and `release` in seconds
+    pub fn new(ceiling: f64, lookahead: f64, release: f64) -> Self {
+        let lookahead = ((lookahead * SAMPLE_RATE) as usize).max(1);
+        Self {
+            ceiling: db_to_linear(ceiling),
+            lookahead,
+            release: time_constant(release),
+            delay: VecDeque::from(vec![(0.0, 0.0); lookahead - 1]),
+            minimum: VecDeque::new(),
+            window: VecDeque::from(vec![1.0; lookahead]),
+            window_sum: lookahead as f64,
+            envelope: 1.0,
+            gain: 1.0,
+            index: 0,
+        }
+    }
+
+    /// Ceiling in dB
+    pub fn set_ceiling(&mut self, ceiling: f64) {
+        self.ceiling = db_to_linear(ceiling);
+    }
+
+    /// Release time in seconds
+    pub fn set_release(&mut self, release: f64) {
+        self.release = time_constant(release);
+    }
+
+    /// Added latency in samples
+    pub fn latency(&self) -> usize {
+        self.lookahead - 1
+    }
+
+    /// Current gain reduction in dB
+    pub fn gain_reduction(&self) -> f64 {
+        -linear_to_db(self.gain)
+    }
+
+    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        let peak = input.0.abs().max(input.1.abs());
+        let required = if peak > self.ceiling { self.ceiling / peak } else { 1.0 };
+
+        // Minimum of the required gain over the lookahead window
+        while let Some(&(_, g)) = self.minimum.back() {
+            if g >= required {
+                self.minimum.pop_back();
+            } else {
+                break;
+            }
+        }
+        self.minimum.push_back((self.index, required));
+        while let Some(&(i, _)) = self.minimum.front() {
+            if i + self.lookahead <= self.index {
+                self.minimum.pop_front();
+            } else {
+                break;
+            }
+        }
+        let held = self.minimum.front().map(|m| m.1).unwrap_or(1.0);
+        self.index += 1;
+
+        self.envelope = if held < self.envelope {
+            held
+        } else {
+            held + (self.envelope - held) * self.release
+        };
+
+        // Moving average turns the steps of the envelope into ramps
+        // that still reach the required gain before the peak is output
+        self.window.push_back(self.envelope);
+        self.window_sum += self.envelope;
+        if let Some(old) = self.window.pop_front() {
+            self.window_sum -= old;
+        }
+        self.gain = (self.window_sum / self.lookahead as f64).min(1.0);
+
+        self.delay.push_back(input);
+        let delayed = self.delay.pop_front().unwrap_or((0.0, 0.0));
+
+        (delayed.0 * self.gain, delayed.1 * self.gain)
+    }
+}
+
+/// Final stage of a rack, an optional stereo-linked compressor
+/// followed by a brickwall limiter.
+///
+/// Reports the total gain reduction for metering.
+pub struct MasterBus {
+    compressor: Option<Compressor>,
+    limiter: Limiter,
+    compressor_reduction: f64,
+}
+
+impl MasterBus {
+    /// Limiter with a ceiling of -0.3 dB and 5ms lookahead
+    pub fn new() -> Self {
+        Self {
+            compressor: None,
+            limiter: Limiter::new(-0.3, 0.005, 0.05),
+            compressor_reduction: 0.0,
+        }
+    }
+
+    pub fn with_compressor(mut self, compressor: Compressor) -> Self {
+        self.compressor = Some(compressor);
+        self
+    }
+
+    pub fn limiter(&mut self) -> &mut Limiter {
+        &mut self.limiter
+    }
+
+    /// Combined gain reduction of compressor and limiter in dB
+    pub fn gain_reduction(&self) -> f64 {
+        self.compressor_reduction + self.limiter.gain_reduction()
+    }
+
+    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        let input = match self.compressor {
+            Some(ref mut compressor) => {
+                let gain = compressor.gain(input.0.abs().max(input.1.abs()));
+                self.compressor_reduction = compressor.gain_reduction();
+                (input.0 * gain, input.1 * gain)
+            }
+            None => input,
+        };
+
+        self.limiter.process(input)
+    }
+}
+
+impl Default for MasterBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,253 @@
+use crate::SAMPLE_RATE;
+use crate::modules::Module;
+use crate::util::clamp;
+
+const PI: f64 = std::f64::consts::PI;
+
+/// Highest cutoff frequency, as a fraction of the sample rate.
+/// `tan` explodes at the nyquist frequency so we stay a bit below it.
+const MAX_CUTOFF: f64 = 0.49;
+
+/// Prewarped integrator gain of a TPT one-pole at `cutoff` Hz
+fn prewarp(cutoff: f64) -> f64 {
+    let cutoff = clamp(cutoff, 1.0, SAMPLE_RATE * MAX_CUTOFF);
+    (PI * cutoff / SAMPLE_RATE).tan()
+}
+
+/// Output of the state-variable filter returned by `get`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SvfMode {
+    Lowpass,
+    Highpass,
+    Bandpass,
+    Notch,
+}
+
+/// Zero-delay-feedback state-variable filter
+/// (trapezoidal integration, after Andrew Simper / Cytomic).
+///
+/// The integrator states are updated in a way that
+/// keeps the filter stable even if cutoff and resonance
+/// are modulated at audio rate.
+///
+/// Inputs:
+///
+/// 0. Signal
+/// 1. Cutoff frequency in Hz
+/// 2. Resonance, 0..1, a Q of 500 at 1.
+///    The filter is linear and does not self-oscillate
+///
+/// Outputs:
+///
+/// 0. Output selected by `mode`
+/// 1. Lowpass
+/// 2. Highpass
+/// 3. Bandpass
+/// 4. Notch
+#[derive(Debug, Clone)]
+pub struct StateVariableFilter {
+    mode: SvfMode,
+    input: f64,
+    g: f64,
+    k: f64,
+    ic1eq: f64,
+    ic2eq: f64,
+    low: f64,
+    band: f64,
+    high: f64,
+}
+
+impl StateVariableFilter {
+    pub fn new(mode: SvfMode, cutoff: f64, resonance: f64) -> Self {
+        let mut filter = Self {
+            mode,
+            input: 0.0,
+            g: 0.0,
+            k: 0.0,
+            ic1eq: 0.0,
+            ic2eq: 0.0,
+            low: 0.0,
+            band: 0.0,
+            high: 0.0,
+        };
+
+        filter.set_cutoff(cutoff);
+        filter.set_resonance(resonance);
+        filter
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: f64) {
+        self.g = prewarp(cutoff);
+    }
+
+    pub fn set_resonance(&mut self, resonance: f64) {
+        // k = 1/Q, k = 0 would be a lossless oscillator
+        self.k = 2.0 * (1.0 - clamp(resonance, 0.0, 0.999));
+    }
+
+    pub fn lowpass(&self) -> f64 {
+        self.low
+    }
+
+    pub fn highpass(&self) -> f64 {
+        self.high
+    }
+
+    pub fn bandpass(&self) -> f64 {
+        self.band
+    }
+
+    pub fn notch(&self) -> f64 {
+        self.low + self.high
+    }
+
+    pub fn process(&mut self, input: f64) -> f64 {
+        let a1 = 1.0 / (1.0 + self.g * (self.g + self.k));
+        let a2 = self.g * a1;
+        let a3 = self.g * a2;
+
+        let v3 = input - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+        self.low = v2;
+        self.band = v1;
+        self.high = input - self.k * v1 - v2;
+
+        match self.mode {
+            SvfMode::Lowpass => self.lowpass(),
+            SvfMode::Highpass => self.highpass(),
+            SvfMode::Bandpass => self.bandpass(),
+            SvfMode::Notch => self.notch(),
+        }
+    }
+}
+
+impl Module for StateVariableFilter {
+    fn get(&mut self) -> f64 {
+        self.process(self.input)
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.input = val,
+            1 => self.set_cutoff(val),
+            2 => self.set_resonance(val),
+            _ => ()
+        }
+    }
+
+    fn outputs(&self) -> usize {
+        5
+    }
+
+    fn get_output(&self, i: usize) -> f64 {
+        match i {
+            1 => self.lowpass(),
+            2 => self.highpass(),
+            3 => self.bandpass(),
+            4 => self.notch(),
+            _ => 0.0
+        }
+    }
+}
+
+/// 4-pole lowpass modeled after the Moog transistor ladder.
+///
+/// Each stage is a zero-delay-feedback one-pole,
+/// the global feedback loop is solved for the linear case
+/// and the result is saturated with `tanh` before
+/// it enters the first stage.
+/// The saturation bounds the energy in the loop,
+/// so the filter self-oscillates at full resonance
+/// instead of blowing up.
+///
+/// Inputs:
+///
+/// 0. Signal
+/// 1. Cutoff frequency in Hz
+/// 2. Resonance, 0..1, self-oscillation at 1
+/// 3. Drive, gain applied before the saturation, 1.0 is clean-ish
+#[derive(Debug, Clone)]
+pub struct LadderFilter {
+    input: f64,
+    // One-pole gain `g / (1 + g)`
+    gain: f64,
+    feedback: f64,
+    drive: f64,
+    stages: [f64; 4],
+}
+
+impl LadderFilter {
+    pub fn new(cutoff: f64, resonance: f64) -> Self {
+        let mut filter = Self {
+            input: 0.0,
+            gain: 0.0,
+            feedback: 0.0,
+            drive: 1.0,
+            stages: [0.0; 4],
+        };
+
+        filter.set_cutoff(cutoff);
+        filter.set_resonance(resonance);
+        filter
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: f64) {
+        let g = prewarp(cutoff);
+        self.gain = g / (1.0 + g);
+    }
+
+    pub fn set_resonance(&mut self, resonance: f64) {
+        self.feedback = 4.0 * clamp(resonance, 0.0, 1.0);
+    }
+
+    pub fn set_drive(&mut self, drive: f64) {
+        self.drive = drive.max(0.0);
+    }
+
+    pub fn process(&mut self, input: f64) -> f64 {
+        let g = self.gain;
+
+        // Contribution of the stage states to the output of the last stage
+        let mut sigma = 0.0;
+        for s in self.stages.iter() {
+            sigma = sigma * g + s * (1.0 - g);
+        }
+
+        let g4 = g * g * g * g;
+        let u = (self.drive * input - self.feedback * sigma) / (1.0 + self.feedback * g4);
+        let mut x = u.tanh();
+
+        for s in self.stages.iter_mut() {
+            let v = (x - *s) * g;
+            let y = v + *s;
+            *s = y + v;
+            x = y;
+        }
+
+        if self.drive > 1.0 {
+            x / self.drive.sqrt()
+        } else {
+            x
+        }
+    }
+}
+
+impl Module for LadderFilter {
+    fn get(&mut self) -> f64 {
+        self.process(self.input)
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.input = val,
+            1 => self.set_cutoff(val),
+            2 => self.set_resonance(val),
+            3 => self.set_drive(val),
+            _ => ()
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::SAMPLE_RATE;
+use crate::reverb::{Reverb, wet_gains};
 
 pub struct DelayLine {
     buffer: Vec<f64>,
@@ -25,6 +26,13 @@ impl DelayLine {
             self.index = 0;
         }
     }
+
+    pub fn reset(&mut self) {
+        for v in self.buffer.iter_mut() {
+            *v = 0.0;
+        }
+        self.index = 0;
+    }
 }
 
 pub struct AllPass {
@@ -48,6 +56,10 @@ impl AllPass {
 
         output
     }
+
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+    }
 }
 
 pub struct Comb {
@@ -91,6 +103,11 @@ impl Comb {
 
         output
     }
+
+    pub fn reset(&mut self) {
+        self.delay_line.reset();
+        self.filter_state = 0.0;
+    }
 }
 
 const FIXED_GAIN: f64 = 0.015;
@@ -106,11 +123,14 @@ pub struct Freeverb {
     combs: [(Comb, Comb); 8],
     allpasses: [(AllPass, AllPass); 4],
     wet_gains: (f64, f64),
+    input_gain: f64,
+    // Parameters as set by the user, in 0..1
     wet: f64,
     width: f64,
     dry: f64,
     dampening: f64,
     room_size: f64,
+    frozen: bool,
 }
 
 // TODO: Hardcode this into the values
@@ -138,11 +158,13 @@ impl Freeverb {
                 (AllPass::new(ALLPASS_TUNING[3]), AllPass::new(ALLPASS_TUNING[3] + STEREO_SPREAD)),
             ],
             wet_gains: (0.0, 0.0),
+            input_gain: FIXED_GAIN,
             wet: 0.0,
             dry: 0.0,
             width: 0.0,
             dampening: 0.0,
             room_size: 0.0,
+            frozen: false,
         };
 
         freeverb.set_wet(1.0);
@@ -153,8 +175,40 @@ impl Freeverb {
         freeverb
     }
 
-    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
-        let input_mixed = (input.0 + input.1) * FIXED_GAIN;
+    fn update_wet_gains(&mut self) {
+        self.wet_gains = wet_gains(self.wet * SCALE_WET, self.width);
+    }
+
+    fn update_combs(&mut self) {
+        let (feedback, dampening) = if self.frozen {
+            self.input_gain = 0.0;
+            (1.0, 0.0)
+        } else {
+            self.input_gain = FIXED_GAIN;
+            (
+                self.room_size * SCALE_ROOM + OFFSET_ROOM,
+                self.dampening * SCALE_DAMPENING,
+            )
+        };
+
+        for combs in self.combs.iter_mut() {
+            combs.0.set_feedback(feedback);
+            combs.1.set_feedback(feedback);
+            combs.0.set_dampening(dampening);
+            combs.1.set_dampening(dampening);
+        }
+    }
+}
+
+impl Default for Freeverb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reverb for Freeverb {
+    fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        let input_mixed = (input.0 + input.1) * self.input_gain;
         let mut out = (0.0, 0.0);
 
         for combs in self.combs.iter_mut() {
@@ -171,40 +225,71 @@ impl Freeverb {
          out.1 * self.wet_gains.0 + out.0 * self.wet_gains.1 + input.1 * self.dry)
     }
 
-    pub fn set_dampening(&mut self, value: f64) {
-        self.dampening = value * SCALE_DAMPENING;
-        for combs in self.combs.iter_mut() {
-            combs.0.set_dampening(self.dampening);
-            combs.1.set_dampening(self.dampening);
-        }
+    fn set_dampening(&mut self, value: f64) {
+        self.dampening = value;
+        self.update_combs();
+    }
+
+    fn dampening(&self) -> f64 {
+        self.dampening
     }
 
-    pub fn set_wet(&mut self, value: f64) {
-        self.wet = value * SCALE_WET;
+    fn set_wet(&mut self, value: f64) {
+        self.wet = value;
         self.update_wet_gains();
     }
 
-    pub fn set_width(&mut self, value: f64) {
+    fn wet(&self) -> f64 {
+        self.wet
+    }
+
+    fn set_width(&mut self, value: f64) {
         self.width = value;
         self.update_wet_gains();
     }
 
-    fn update_wet_gains(&mut self) {
-        self.wet_gains = (
-            self.wet * ((1.0 + self.width) / 2.0),
-            self.wet * ((1.0 - self.width) / 2.0),
-        );
+    fn width(&self) -> f64 {
+        self.width
     }
 
-    pub fn set_room_size(&mut self, value: f64) {
-        self.room_size = value * SCALE_ROOM + OFFSET_ROOM;
-        for combs in self.combs.iter_mut() {
-            combs.0.set_feedback(self.room_size);
-            combs.1.set_feedback(self.room_size);
-        }
+    fn set_room_size(&mut self, value: f64) {
+        self.room_size = value;
+        self.update_combs();
     }
 
-    pub fn set_dry(&mut self, value: f64) {
+    fn room_size(&self) -> f64 {
+        self.room_size
+    }
+
+    fn set_dry(&mut self, value: f64) {
         self.dry = value;
     }
+
+    fn dry(&self) -> f64 {
+        self.dry
+    }
+
+    /// In freeze mode the input is muted and the combs
+    /// feed back without loss, so the current tail is held forever.
+    fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+        self.update_combs();
+    }
+
+    fn frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Clear all delay lines, e.g. when switching patches
+    /// so the old tail is not carried over.
+    fn reset(&mut self) {
+        for combs in self.combs.iter_mut() {
+            combs.0.reset();
+            combs.1.reset();
+        }
+        for allpasses in self.allpasses.iter_mut() {
+            allpasses.0.reset();
+            allpasses.1.reset();
+        }
+    }
 }
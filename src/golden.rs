@@ -0,0 +1,220 @@
+//! Golden output tests.
+//!
+//! A module or rack is rendered for a number of samples with a fixed seed
+//! and compared to a reference WAV file stored in `tests/golden`.
+//! A missing reference fails the test, set `PATCHWORK_BLESS=1`
+//! to write new references or to replace them after an intended change.
+//! On a mismatch the rendered output and the difference are written
+//! to `target/golden` next to the error metrics.
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::SAMPLE_RATE;
+use crate::modules::Module;
+use crate::rack::Rack;
+
+/// Render `samples` samples of output 0 of `module`,
+/// `drive` is called before every sample to set its inputs
+pub fn render<F>(module: &mut dyn Module, samples: usize, mut drive: F) -> Vec<f64>
+    where F: FnMut(&mut dyn Module, usize)
+{
+    (0..samples)
+        .map(|t| {
+            drive(module, t);
+            module.get()
+        })
+        .collect()
+}
+
+/// Render `samples` stereo samples of `rack`, interleaved left and right,
+/// `drive` is called before every sample to send control values
+pub fn render_rack<F>(rack: &mut Rack, samples: usize, mut drive: F) -> Vec<f64>
+    where F: FnMut(&mut Rack, usize)
+{
+    let mut out = Vec::with_capacity(samples * 2);
+    for t in 0..samples {
+        drive(rack, t);
+        let (l, r) = rack.get();
+        out.push(l);
+        out.push(r);
+    }
+    out
+}
+
+/// Difference between a rendered output and its reference
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    pub max_error: f64,
+    pub rms_error: f64,
+    /// Samples differing by more than the tolerance
+    pub mismatches: usize,
+    pub first_mismatch: Option<usize>,
+    /// Signal to error ratio in dB
+    pub snr: f64,
+}
+
+impl Metrics {
+    /// Compare two signals of the same length
+    pub fn compare(actual: &[f64], expected: &[f64], tolerance: f64) -> Self {
+        let mut max_error: f64 = 0.0;
+        let mut error_power = 0.0;
+        let mut signal_power = 0.0;
+        let mut mismatches = 0;
+        let mut first_mismatch = None;
+
+        for (i, (a, e)) in actual.iter().zip(expected.iter()).enumerate() {
+            let error = (a - e).abs();
+            if error > tolerance {
+                mismatches += 1;
+                first_mismatch = first_mismatch.or(Some(i));
+            }
+            max_error = max_error.max(error);
+            error_power += error * error;
+            signal_power += e * e;
+        }
+
+        let len = actual.len().max(1) as f64;
+        Self {
+            max_error,
+            rms_error: (error_power / len).sqrt(),
+            mismatches,
+            first_mismatch,
+            snr: 10.0 * (signal_power / error_power).log10(),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.mismatches == 0
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} samples off, max error {:.3e}, rms error {:.3e}, snr {:.1} dB",
+            self.mismatches, self.max_error, self.rms_error, self.snr)?;
+        if let Some(i) = self.first_mismatch {
+            write!(f, ", first at sample {}", i)?;
+        }
+        Ok(())
+    }
+}
+
+/// A named reference output
+#[derive(Debug, Clone)]
+pub struct Golden {
+    name: String,
+    channels: u16,
+    tolerance: f64,
+    dir: PathBuf,
+}
+
+impl Golden {
+    /// Mono reference `tests/golden/<name>.wav`
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            channels: 1,
+            tolerance: 1e-6,
+            dir: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden"),
+        }
+    }
+
+    /// Interleaved stereo, as rendered by `render_rack`
+    pub fn stereo(mut self) -> Self {
+        self.channels = 2;
+        self
+    }
+
+    /// Largest difference per sample that still passes
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Directory of the reference files
+    pub fn with_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    fn bless() -> bool {
+        std::env::var_os("PATCHWORK_BLESS").is_some()
+    }
+
+    fn write_wav(&self, path: &PathBuf, samples: &[f64]) -> Result<(), String> {
+        let spec = hound::WavSpec {
+            channels: self.channels,
+            sample_rate: SAMPLE_RATE as u32,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let error = |e: hound::Error| format!("Cannot write {}: {}", path.display(), e);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+        }
+        let mut writer = hound::WavWriter::create(path, spec).map_err(error)?;
+        for v in samples {
+            writer.write_sample(*v as f32).map_err(error)?;
+        }
+        writer.finalize().map_err(error)
+    }
+
+    /// Write the rendered output and its difference to the reference
+    /// to `target/golden`, returns the path of the output
+    fn write_failure(&self, samples: &[f64], expected: &[f64]) -> Result<PathBuf, String> {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("golden");
+        let actual = dir.join(format!("{}.actual.wav", self.name));
+        self.write_wav(&actual, samples)?;
+
+        if !expected.is_empty() {
+            let diff: Vec<f64> = samples.iter().zip(expected.iter()).map(|(a, e)| a - e).collect();
+            self.write_wav(&dir.join(format!("{}.diff.wav", self.name)), &diff)?;
+        }
+        Ok(actual)
+    }
+
+    /// Compare `samples` to the reference WAV,
+    /// which is written instead with `PATCHWORK_BLESS` set
+    pub fn check_wav(&self, samples: &[f64]) -> Result<Metrics, String> {
+        let path = self.dir.join(format!("{}.wav", self.name));
+        if Self::bless() {
+            self.write_wav(&path, samples)?;
+            return Ok(Metrics::compare(samples, samples, self.tolerance));
+        }
+        if !path.exists() {
+            let actual = self.write_failure(samples, &[])?;
+            return Err(format!("{}: {} is missing, set PATCHWORK_BLESS=1 to write it, output in {}",
+                self.name, path.display(), actual.display()));
+        }
+
+        let mut reader = hound::WavReader::open(&path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        if reader.spec().channels != self.channels {
+            return Err(format!("{} has {} channels instead of {}",
+                path.display(), reader.spec().channels, self.channels));
+        }
+        let expected = reader.samples::<f32>()
+            .map(|v| v.map(f64::from))
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+
+        // Compare with the precision of the stored samples
+        let stored: Vec<f64> = samples.iter().map(|v| f64::from(*v as f32)).collect();
+        let metrics = Metrics::compare(&stored, &expected, self.tolerance);
+        if metrics.passed() && stored.len() == expected.len() {
+            return Ok(metrics);
+        }
+
+        let actual = self.write_failure(samples, &expected)?;
+        if stored.len() != expected.len() {
+            Err(format!("{}: rendered {} samples, the reference has {}, output in {}",
+                self.name, stored.len(), expected.len(), actual.display()))
+        } else {
+            Err(format!("{}: {}, output and difference in {}",
+                self.name, metrics, actual.parent().unwrap().display()))
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use crate::SAMPLE_RATE;
+use crate::karplus_strong::{KarplusStrong, StringPreset};
+use crate::modules::Module;
+use crate::pitch;
+use crate::random::RngSource;
+use crate::trigger::Trigger;
+use crate::util::clamp;
+
+/// Decay time of choked strings in seconds
+const CHOKE_DECAY: f64 = 0.05;
+/// Decay time of fully palm muted strings in seconds
+const PALM_MUTE_DECAY: f64 = 0.25;
+
+/// MIDI note numbers of the open strings, lowest string first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringTuning {
+    pub strings: Vec<u8>,
+}
+
+impl StringTuning {
+    pub fn new(strings: Vec<u8>) -> Self {
+        Self { strings }
+    }
+
+    /// E A D G B E
+    pub fn standard() -> Self {
+        Self::new(vec![40, 45, 50, 55, 59, 64])
+    }
+
+    /// D A D G B E
+    pub fn drop_d() -> Self {
+        Self::new(vec![38, 45, 50, 55, 59, 64])
+    }
+
+    /// D G D G B D
+    pub fn open_g() -> Self {
+        Self::new(vec![38, 43, 50, 55, 59, 62])
+    }
+
+    /// D A D G A D
+    pub fn dadgad() -> Self {
+        Self::new(vec![38, 45, 50, 55, 57, 62])
+    }
+}
+
+/// Fret per string, lowest string first,
+/// `None` for strings that are not played
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    pub frets: Vec<Option<u8>>,
+}
+
+impl Chord {
+    /// Frets from a tab, -1 marks muted strings
+    pub fn from_frets(frets: &[i8]) -> Self {
+        Self {
+            frets: frets.iter().map(|f| if *f < 0 { None } else { Some(*f as u8) }).collect(),
+        }
+    }
+
+    /// Common open and barre chord shapes for standard tuning,
+    /// e.g. "C", "Am", "G7", "Bb"
+    pub fn named(name: &str) -> Option<Self> {
+        let frets: [i8; 6] = match name {
+            "C" => [-1, 3, 2, 0, 1, 0],
+            "C7" => [-1, 3, 2, 3, 1, 0],
+            "D" => [-1, -1, 0, 2, 3, 2],
+            "Dm" => [-1, -1, 0, 2, 3, 1],
+            "D7" => [-1, -1, 0, 2, 1, 2],
+            "E" => [0, 2, 2, 1, 0, 0],
+            "Em" => [0, 2, 2, 0, 0, 0],
+            "E7" => [0, 2, 0, 1, 0, 0],
+            "F" => [1, 3, 3, 2, 1, 1],
+            "Fm" => [1, 3, 3, 1, 1, 1],
+            "G" => [3, 2, 0, 0, 0, 3],
+            "Gm" => [3, 5, 5, 3, 3, 3],
+            "G7" => [3, 2, 0, 0, 0, 1],
+            "A" => [-1, 0, 2, 2, 2, 0],
+            "Am" => [-1, 0, 2, 2, 1, 0],
+            "A7" => [-1, 0, 2, 0, 2, 0],
+            "A#" | "Bb" => [-1, 1, 3, 3, 3, 1],
+            "B" => [-1, 2, 4, 4, 4, 2],
+            "Bm" => [-1, 2, 4, 4, 3, 2],
+            "B7" => [-1, 2, 1, 2, 0, 2],
+            _ => return None,
+        };
+        Some(Self::from_frets(&frets))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From the lowest to the highest string
+    Down,
+    /// From the highest to the lowest string
+    Up,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Strum {
+    pub direction: Direction,
+    /// Time between two strings in seconds
+    pub speed: f64,
+    pub velocity: f64,
+}
+
+impl Strum {
+    pub fn down(velocity: f64) -> Self {
+        Self { direction: Direction::Down, speed: 0.02, velocity }
+    }
+
+    pub fn up(velocity: f64) -> Self {
+        Self { direction: Direction::Up, speed: 0.015, velocity }
+    }
+}
+
+/// What happens to ringing strings that are not part of the next chord
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sustain {
+    LetRing,
+    /// Muted by the fretting hand
+    Choke,
+}
+
+/// Plucked string waiting for its turn in a strum
+#[derive(Debug, Clone, Copy)]
+struct Pending {
+    delay: usize,
+    fret: u8,
+    velocity: f64,
+}
+
+/// Guitar built from one Karplus-Strong string per string of the tuning.
+///
+/// Inputs:
+///
+/// 0. Down strum trigger
+/// 1. Up strum trigger
+/// 2. Chord, index into the chords set with `with_chords`
+/// 3. Velocity, 0..1
+/// 4. Palm mute, 0..1
+pub struct Guitar {
+    strings: Vec<KarplusStrong>,
+    pending: Vec<Option<Pending>>,
+    // Strings muted by `choke` until their next pluck
+    choked: Vec<bool>,
+    tuning: StringTuning,
+    pitch: pitch::Tuning,
+    capo: u8,
+    decay: f64,
+    brightness: f64,
+    palm_mute: f64,
+    sustain: Sustain,
+    chords: Vec<Chord>,
+    chord: usize,
+    velocity: f64,
+    down: Trigger,
+    up: Trigger,
+}
+
+impl Guitar {
+    pub fn new(tuning: StringTuning, preset: StringPreset) -> Self {
+        let strings: Vec<KarplusStrong> = tuning.strings.iter()
+            .map(|note| {
+                let freq = pitch::midi_to_freq(*note as f64);
+                let mut string = KarplusStrong::with_preset(freq, preset);
+                // Strings are silent until plucked
+                string.pluck(0.0);
+                string
+            })
+            .collect();
+        let (decay, brightness) = strings.first()
+            .map(|s| (s.decay(), s.brightness()))
+            .unwrap_or((1.0, 0.5));
+
+        Self {
+            strings,
+            pending: vec![None; tuning.strings.len()],
+            choked: vec![false; tuning.strings.len()],
+            tuning,
+            pitch: pitch::Tuning::standard(),
+            capo: 0,
+            decay,
+            brightness,
+            palm_mute: 0.0,
+            sustain: Sustain::LetRing,
+            chords: Vec::new(),
+            chord: 0,
+            velocity: 1.0,
+            down: Trigger::new(),
+            up: Trigger::new(),
+        }
+    }
+
+    pub fn with_capo(mut self, capo: u8) -> Self {
+        self.capo = capo;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.set_seed(seed);
+        self
+    }
+
+    /// Tuning the frets are played in, 12 tone equal temperament by default
+    pub fn with_pitch_tuning(mut self, pitch: pitch::Tuning) -> Self {
+        self.pitch = pitch;
+        self
+    }
+
+    pub fn with_sustain(mut self, sustain: Sustain) -> Self {
+        self.sustain = sustain;
+        self
+    }
+
+    /// Chords selected by the chord input
+    pub fn with_chords(mut self, chords: Vec<Chord>) -> Self {
+        self.chords = chords;
+        self
+    }
+
+    pub fn set_capo(&mut self, capo: u8) {
+        self.capo = capo;
+    }
+
+    pub fn set_sustain(&mut self, sustain: Sustain) {
+        self.sustain = sustain;
+    }
+
+    /// 0 lets the strings ring, 1 mutes them with the palm,
+    /// choked strings stay muted
+    pub fn set_palm_mute(&mut self, amount: f64) {
+        self.palm_mute = clamp(amount, 0.0, 1.0);
+        for i in 0..self.strings.len() {
+            if !self.choked[i] {
+                self.damp(i);
+            }
+        }
+    }
+
+    /// Frequency of `string` at `fret`, including the capo
+    pub fn freq(&self, string: usize, fret: u8) -> f64 {
+        self.pitch.freq(self.tuning.strings[string] as f64 + self.capo as f64 + fret as f64)
+    }
+
+    /// Set decay and brightness of a string from the palm mute amount
+    fn damp(&mut self, string: usize) {
+        let decay = self.decay + (PALM_MUTE_DECAY - self.decay) * self.palm_mute;
+        let brightness = self.brightness * (1.0 - 0.8 * self.palm_mute);
+        self.strings[string].set_decay(decay);
+        self.strings[string].set_brightness(brightness);
+    }
+
+    /// Pluck a single string right away
+    pub fn pluck(&mut self, string: usize, fret: u8, velocity: f64) {
+        let freq = self.freq(string, fret);
+        self.choked[string] = false;
+        self.damp(string);
+        self.strings[string].set_freq(freq);
+        self.strings[string].pluck(velocity);
+    }
+
+    /// Stop a string from ringing
+    pub fn choke(&mut self, string: usize) {
+        self.pending[string] = None;
+        self.choked[string] = true;
+        self.strings[string].set_decay(CHOKE_DECAY);
+    }
+
+    /// Strum `chord`, the strings are plucked one after another
+    pub fn strum(&mut self, chord: &Chord, strum: Strum) {
+        let step = (strum.speed.max(0.0) * SAMPLE_RATE) as usize;
+        let played = chord.frets.iter().take(self.strings.len()).filter(|f| f.is_some()).count();
+
+        let mut order = 0;
+        for i in 0..self.strings.len() {
+            let string = match strum.direction {
+                Direction::Down => i,
+                Direction::Up => self.strings.len() - 1 - i,
+            };
+
+            match chord.frets.get(string).cloned().flatten() {
+                Some(fret) => {
+                    // Later strings in a strum are hit a bit softer
+                    let velocity = strum.velocity * (1.0 - 0.2 * order as f64 / played as f64);
+                    self.pending[string] = Some(Pending { delay: order * step, fret, velocity });
+                    order += 1;
+                }
+                None => if self.sustain == Sustain::Choke {
+                    self.choke(string);
+                }
+            }
+        }
+    }
+
+    fn strum_selected(&mut self, direction: Direction) {
+        if self.chords.is_empty() {
+            return;
+        }
+
+        let chord = self.chords[self.chord.min(self.chords.len() - 1)].clone();
+        let strum = match direction {
+            Direction::Down => Strum::down(self.velocity),
+            Direction::Up => Strum::up(self.velocity),
+        };
+        self.strum(&chord, strum);
+    }
+}
+
+impl Module for Guitar {
+    fn get(&mut self) -> f64 {
+        for i in 0..self.strings.len() {
+            if let Some(mut pending) = self.pending[i] {
+                if pending.delay == 0 {
+                    self.pending[i] = None;
+                    self.pluck(i, pending.fret, pending.velocity);
+                } else {
+                    pending.delay -= 1;
+                    self.pending[i] = Some(pending);
+                }
+            }
+        }
+
+        self.strings.iter_mut().map(|s| s.get()).sum::<f64>() / self.strings.len() as f64
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 if self.down.process(val) => self.strum_selected(Direction::Down),
+            1 if self.up.process(val) => self.strum_selected(Direction::Up),
+            2 => self.chord = val.round().max(0.0) as usize,
+            3 => self.velocity = clamp(val, 0.0, 1.0),
+            4 => self.set_palm_mute(val),
+            _ => ()
+        }
+    }
+
+    /// Applies to the next plucked strings
+    fn set_tuning(&mut self, tuning: &pitch::Tuning) {
+        self.pitch = tuning.clone();
+    }
+
+    /// Each string gets its own seed derived from `seed`
+    fn set_seed(&mut self, seed: u64) {
+        let mut seeds = RngSource::new(seed);
+        for string in self.strings.iter_mut() {
+            string.set_seed(seeds.next_seed());
+        }
+    }
+}
@@ -1,84 +1,431 @@
 use crate::modules::Module;
 use crate::SAMPLE_RATE;
+use crate::delay::{DelayLine, FractionalAllpass, Interpolation};
+use crate::trigger::Trigger;
+use crate::util::clamp;
 
-use rand::{Rng, thread_rng};
-use rand::rngs::ThreadRng;
+use rand::{FromEntropy, Rng, SeedableRng};
+use rand::rngs::StdRng;
 
+const PI: f64 = std::f64::consts::PI;
+
+/// Lowest playable frequency, sets the size of the delay line
+pub const MIN_FREQ: f64 = 20.0;
+
+/// Number of allpasses simulating the stiffness of the string
+const DISPERSION_STAGES: usize = 4;
+
+/// Split the loop length for `freq` into a whole number of samples
+/// and a fractional delay of 0.5..1.5 samples for the tuning allpass.
+///
+/// `filter_delay` is the delay of the other filters in the loop
+/// at `freq`, e.g. 0.5 for a two point average.
+pub fn loop_delay(freq: f64, filter_delay: f64) -> (usize, f64) {
+    let length = (SAMPLE_RATE / freq - filter_delay).max(1.5);
+    let whole = (length - 0.5).floor();
+    (whole as usize, length - whole)
+}
+
+/// Signal written to the string when it is plucked
+#[derive(Debug, Clone, PartialEq)]
+pub enum Excitation {
+    /// Uniform noise in -1..1
+    Noise,
+    /// Random values of ±1, louder and brighter than `Noise`
+    BinaryNoise,
+    /// One saw wave cycle
+    Saw,
+    /// One pulse wave cycle with the given width 0..1
+    Pulse(f64),
+    /// Noise through a one-pole lowpass,
+    /// brightness 0..1 with 1 being unfiltered
+    FilteredNoise(f64),
+    /// An arbitrary signal, stretched to one period
+    Buffer(Vec<f64>),
+    /// The most recent period of the audio input.
+    /// Silent if the string has no audio input
+    Input,
+}
+
+impl Excitation {
+    /// Fill `out` with one period of the excitation
+    pub fn fill<R: Rng>(&self, out: &mut [f64], rng: &mut R) {
+        let len = out.len() as f64;
+        match self {
+            Excitation::Noise => {
+                for v in out.iter_mut() {
+                    *v = rng.gen_range(-1.0, 1.0);
+                }
+            }
+            Excitation::BinaryNoise => {
+                for v in out.iter_mut() {
+                    *v = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+                }
+            }
+            Excitation::Saw => {
+                for (i, v) in out.iter_mut().enumerate() {
+                    *v = i as f64 / len * 2.0 - 1.0;
+                }
+            }
+            Excitation::Pulse(width) => {
+                for (i, v) in out.iter_mut().enumerate() {
+                    *v = if (i as f64) < len * width { 1.0 } else { -1.0 };
+                }
+            }
+            Excitation::FilteredNoise(brightness) => {
+                let brightness = clamp(*brightness, 0.01, 1.0);
+                let mut state = 0.0;
+                for v in out.iter_mut() {
+                    state += (rng.gen_range(-1.0, 1.0) - state) * brightness;
+                    // Compensate for the energy removed by the filter
+                    *v = state / brightness.sqrt();
+                }
+            }
+            Excitation::Buffer(buffer) if !buffer.is_empty() => {
+                let step = buffer.len() as f64 / len;
+                for (i, v) in out.iter_mut().enumerate() {
+                    let pos = i as f64 * step;
+                    let a = buffer[pos as usize];
+                    let b = buffer.get(pos as usize + 1).cloned().unwrap_or(a);
+                    *v = a + (b - a) * pos.fract();
+                }
+            }
+            Excitation::Buffer(_) | Excitation::Input => {
+                for v in out.iter_mut() {
+                    *v = 0.0;
+                }
+            }
+        }
+    }
+}
+
+/// Comb filter simulating where the string is picked,
+/// 0.5 picks at the middle and removes all even harmonics.
+/// A position of 0 leaves the excitation unchanged.
+pub fn pick(excitation: &mut [f64], position: f64) {
+    let offset = (clamp(position, 0.0, 1.0) * excitation.len() as f64).round() as usize;
+    if offset == 0 || offset >= excitation.len() {
+        return;
+    }
+
+    // Backwards, so the values subtracted are still unfiltered
+    for i in (offset..excitation.len()).rev() {
+        excitation[i] -= excitation[i - offset];
+    }
+}
+
+/// Chain of first order allpasses,
+/// delays low frequencies more than high ones like a stiff string
+#[derive(Debug, Clone)]
+struct Dispersion {
+    coefficient: f64,
+    // Last input and output of each stage
+    states: [(f64, f64); DISPERSION_STAGES],
+}
+
+impl Dispersion {
+    fn new() -> Self {
+        Self {
+            coefficient: 0.0,
+            states: [(0.0, 0.0); DISPERSION_STAGES],
+        }
+    }
+
+    /// Stiffness 0..1, 0 disables the dispersion
+    fn set_stiffness(&mut self, stiffness: f64) {
+        self.coefficient = -0.5 * clamp(stiffness, 0.0, 1.0);
+    }
+
+    /// Phase delay in samples at `freq`
+    fn delay(&self, freq: f64) -> f64 {
+        if self.coefficient == 0.0 {
+            return 0.0;
+        }
+
+        let w = 2.0 * PI * freq / SAMPLE_RATE;
+        let a = self.coefficient;
+        // H(z) = (a + z^-1) / (1 + a z^-1)
+        let phase = (-w.sin()).atan2(a + w.cos()) - (-a * w.sin()).atan2(1.0 + a * w.cos());
+        -phase / w * DISPERSION_STAGES as f64
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        if self.coefficient == 0.0 {
+            return input;
+        }
+
+        let mut x = input;
+        for (x1, y1) in self.states.iter_mut() {
+            let y = self.coefficient * (x - *y1) + *x1;
+            *x1 = x;
+            *y1 = y;
+            x = y;
+        }
+        x
+    }
+}
+
+/// Sets of string parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringPreset {
+    Nylon,
+    Steel,
+    Harp,
+    Piano,
+}
+
+/// Plucked string with the extensions by Jaffe and Smith.
+///
+/// The decay time is independent of the pitch,
+/// the brightness sets how fast high partials decay relative to the fundamental.
+/// Stiffness adds dispersion, stretching the partials like on a piano string.
+/// Soft plucks are filtered darker, depending on the dynamics amount.
+///
+/// Inputs:
+///
+/// 0. Frequency in Hz
+/// 1. Trigger, plucks the string again
+/// 2. Velocity of the next pluck, 0..1
+/// 3. Decay time (T60) of the fundamental in seconds
+/// 4. Audio input, used by `Excitation::Input`
+/// 5. Pick position, 0..1
+/// 6. Brightness, 0..1
+/// 7. Stiffness, 0..1
 pub struct KarplusStrong {
-    pub wavetable: Vec<f64>,
-    pub index: usize,
-    size: usize,
+    line: DelayLine,
+    period: usize,
+    tuning: FractionalAllpass,
+    dispersion: Dispersion,
     blend: f64,
     strech: f64,
-    rng: ThreadRng,
+    freq: f64,
+    velocity: f64,
+    decay: f64,
+    brightness: f64,
+    dynamics: f64,
+    // Gain of the feedback loop, derived from the decay time
+    gain: f64,
+    excitation: Excitation,
+    pick_position: f64,
+    // Preallocated for the longest period, so plucking never allocates
+    excitation_buffer: Vec<f64>,
+    input: DelayLine,
+    // Latest value of the audio input, recorded once per sample
+    input_sample: f64,
+    trigger: Trigger,
+    rng: StdRng,
+    // The pluck of `new` waits for the first sample,
+    // so it uses the settings and seed applied until then
+    pending_pluck: bool,
 }
 
 impl Module for KarplusStrong {
     fn get(&mut self) -> f64 {
-        let avg = 
-            if self.rng.gen_bool(self.strech) {
-                if self.rng.gen_bool(self.blend) {
-                    (self.current() + self.prev()) * 0.5
-                } else {
-                    (self.current() + self.prev()) * -0.5
-                }
-            } else {
-                if self.rng.gen_bool(self.blend) {
-                    self.current() * 1.0
-                } else {
-                    self.current() * -1.0
-                }
-            };
-        self.write(avg);
-
-        self.index += 1;
-        if self.index == self.size {
-            self.index = 0;
+        if self.pending_pluck {
+            self.pluck(1.0);
         }
+        self.input.write(self.input_sample);
+
+        let next = self.line.tap(self.period);
+        let current = self.line.tap(self.period + 1);
+        let prev = self.line.tap(self.period + 2);
+
+        // Symmetric three point lowpass, one sample of delay at all frequencies
+        let (outer, center) = self.damping();
+        let damped = if self.rng.gen_bool(self.strech) {
+            center * current + outer * (next + prev)
+        } else {
+            current
+        };
+        let damped = if self.rng.gen_bool(self.blend) { damped } else { -damped };
 
-        avg
+        let out = self.tuning.process(self.dispersion.process(damped * self.gain));
+        self.line.write(out);
+
+        out
     }
 
-    fn set_input(&mut self, i: usize, val: f64) {}
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.set_freq(val),
+            1 if self.trigger.process(val) => self.pluck(self.velocity),
+            2 => self.velocity = clamp(val, 0.0, 1.0),
+            3 => self.set_decay(val),
+            4 => self.input_sample = val,
+            5 => self.set_pick_position(val),
+            6 => self.set_brightness(val),
+            7 => self.set_stiffness(val),
+            _ => ()
+        }
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
 }
 
 impl KarplusStrong {
     pub fn new(freq: f64, blend: f64, strech: f64) -> Self {
-        let p = (SAMPLE_RATE / freq + 0.5) as usize;
-
-        let mut wavetable = Vec::with_capacity(p);
-        let mut rng = thread_rng();
-        for _i in 0..p {
-            // wavetable.push(rng.gen_range(-1.0, 1.0));
-            if rng.gen_bool(0.5) {
-                wavetable.push(1.0);
-            } else {
-                wavetable.push(-1.0);
-            }
-        }
+        let max_period = (SAMPLE_RATE / MIN_FREQ) as usize + 1;
+
+        let mut ks = Self {
+            line: DelayLine::new(max_period + 1, Interpolation::Linear),
+            period: 1,
+            tuning: FractionalAllpass::new(1.0),
+            dispersion: Dispersion::new(),
+            blend,
+            strech: 1.0 / strech,
+            freq,
+            velocity: 1.0,
+            decay: 2.0,
+            brightness: 0.0,
+            dynamics: 0.0,
+            gain: 1.0,
+            excitation: Excitation::BinaryNoise,
+            pick_position: 0.0,
+            excitation_buffer: vec![0.0; max_period + 2],
+            input: DelayLine::new(max_period + 1, Interpolation::Linear),
+            input_sample: 0.0,
+            trigger: Trigger::new(),
+            rng: StdRng::from_entropy(),
+            pending_pluck: true,
+        };
 
-        let strech = 1.0 / strech;
-        Self { wavetable, size: p, index: 0, blend, strech, rng }
+        ks.set_freq(freq);
+        ks
     }
 
-    fn prev(&self) -> f64 {
-        if self.index == 0 {
-            self.wavetable[self.size - 1]
-        } else {
-            self.wavetable[self.index - 1]
-        }
+    /// String with the parameters of `preset`
+    pub fn with_preset(freq: f64, preset: StringPreset) -> Self {
+        let mut ks = Self::new(freq, 1.0, 1.0);
+        let (decay, brightness, stiffness, excitation, pick) = match preset {
+            StringPreset::Nylon => (1.5, 0.3, 0.0, Excitation::FilteredNoise(0.5), 0.15),
+            StringPreset::Steel => (4.0, 0.7, 0.1, Excitation::BinaryNoise, 0.1),
+            StringPreset::Harp => (3.0, 0.4, 0.0, Excitation::Noise, 0.5),
+            StringPreset::Piano => (6.0, 0.6, 0.6, Excitation::FilteredNoise(0.8), 0.12),
+        };
+
+        ks.set_decay(decay);
+        ks.set_brightness(brightness);
+        ks.set_stiffness(stiffness);
+        ks.set_dynamics(1.0);
+        ks.set_excitation(excitation);
+        ks.set_pick_position(pick);
+        ks
+    }
+
+    /// Use `excitation` from the next pluck on
+    pub fn with_excitation(mut self, excitation: Excitation) -> Self {
+        self.excitation = excitation;
+        self
+    }
+
+    pub fn set_excitation(&mut self, excitation: Excitation) {
+        self.excitation = excitation;
+    }
+
+    /// Pick position, 0..1, see `pick`
+    pub fn set_pick_position(&mut self, position: f64) {
+        self.pick_position = clamp(position, 0.0, 1.0);
+    }
+
+    /// Time in seconds for the fundamental to decay by 60 dB
+    pub fn set_decay(&mut self, decay: f64) {
+        self.decay = decay.max(0.001);
+        self.update();
+    }
+
+    pub fn decay(&self) -> f64 {
+        self.decay
     }
 
-    fn current(&self) -> f64 {
-        self.wavetable[self.index]
+    pub fn brightness(&self) -> f64 {
+        self.brightness
     }
 
-    fn write(&mut self, val: f64) {
-        if self.index == 0 {
-            self.wavetable[self.size - 1] = val;
+    /// 0 loses high partials quickly, 1 lets them ring as long as the fundamental.
+    /// With a low brightness, high notes decay faster than the decay time
+    pub fn set_brightness(&mut self, brightness: f64) {
+        self.brightness = clamp(brightness, 0.0, 1.0);
+        self.update();
+    }
+
+    /// 0 for an ideal string, higher values stretch the partials
+    pub fn set_stiffness(&mut self, stiffness: f64) {
+        self.dispersion.set_stiffness(stiffness);
+        self.update();
+    }
+
+    /// How much darker soft plucks sound, 0..1
+    pub fn set_dynamics(&mut self, dynamics: f64) {
+        self.dynamics = clamp(dynamics, 0.0, 1.0);
+    }
+
+    /// Change the pitch without exciting the string again
+    pub fn set_freq(&mut self, freq: f64) {
+        self.freq = freq.max(MIN_FREQ);
+        self.update();
+    }
+
+    /// Outer and center taps of the loop filter
+    fn damping(&self) -> (f64, f64) {
+        ((1.0 - self.brightness) * 0.25, (1.0 + self.brightness) * 0.5)
+    }
+
+    /// Recompute the loop length and gain
+    fn update(&mut self) {
+        let (period, fraction) = loop_delay(self.freq, 1.0 + self.dispersion.delay(self.freq));
+        self.period = period;
+        self.tuning.set_delay(fraction);
+
+        // Compensate the loss of the loop filter at the fundamental
+        let (outer, center) = self.damping();
+        let w = 2.0 * PI * self.freq / SAMPLE_RATE;
+        let damping = center + 2.0 * outer * w.cos();
+        let target = 0.001_f64.powf(1.0 / (self.decay * self.freq));
+        self.gain = (target / damping).min(1.0);
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.set_seed(seed);
+        self
+    }
+
+    /// Excite the string, scaled by `velocity`
+    pub fn pluck(&mut self, velocity: f64) {
+        self.pending_pluck = false;
+        let excitation = &mut self.excitation_buffer[..self.period + 2];
+
+        if self.excitation == Excitation::Input {
+            let len = excitation.len();
+            for (i, v) in excitation.iter_mut().enumerate() {
+                *v = self.input.tap(len - i);
+            }
         } else {
-            self.wavetable[self.index - 1] = val;
+            self.excitation.fill(excitation, &mut self.rng);
+        }
+        pick(excitation, self.pick_position);
+
+        // DC would never decay when the loop gain is capped at 1
+        let mean = excitation.iter().sum::<f64>() / excitation.len() as f64;
+        for v in excitation.iter_mut() {
+            *v -= mean;
+        }
+
+        // Dynamic level lowpass, from the fundamental at velocity 0
+        // to fully open at velocity 1
+        if self.dynamics > 0.0 {
+            let cutoff = (self.freq * 2.0_f64.powf(8.0 * velocity)).min(SAMPLE_RATE * 0.45);
+            let coefficient = (-2.0 * PI * cutoff / SAMPLE_RATE).exp();
+            let mut state = 0.0;
+            for v in excitation.iter_mut() {
+                state = *v * (1.0 - coefficient) + state * coefficient;
+                *v += (state - *v) * self.dynamics;
+            }
+        }
+
+        for v in excitation.iter() {
+            self.line.write(v * velocity);
         }
     }
 }
-
@@ -0,0 +1,230 @@
+use rand::{FromEntropy, Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::SAMPLE_RATE;
+use crate::modules::Module;
+use crate::tempo::Division;
+use crate::trigger::Trigger;
+use crate::util::clamp;
+
+const PI: f64 = std::f64::consts::PI;
+const TWOPI: f64 = std::f64::consts::PI * 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    SawUp,
+    SawDown,
+    Square,
+    /// New random value once per cycle
+    SampleAndHold,
+    /// Random values with cosine interpolation in between
+    SmoothRandom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarity {
+    /// -1..1
+    Bipolar,
+    /// 0..1
+    Unipolar,
+}
+
+/// Low frequency oscillator
+///
+/// Inputs:
+///
+/// 0. Frequency in Hz, disables tempo sync
+/// 1. Retrigger, restarts the cycle and the fade-in on a rising edge
+/// 2. Phase offset, 0..1
+/// 3. Tempo in BPM, enables tempo sync
+/// 4. Division, control value 0..1 selecting one of `tempo::DIVISIONS`
+/// 5. Fade-in time in seconds
+pub struct Lfo {
+    shape: LfoShape,
+    polarity: Polarity,
+    phase: f64,
+    step: f64,
+    offset: f64,
+    // Phase including the offset of the last sample
+    shown_phase: f64,
+    freq: f64,
+    // bpm and division while synced to a tempo
+    sync: Option<(f64, Division)>,
+    division: Division,
+    retrigger: Trigger,
+    fade_step: f64,
+    fade: f64,
+    // Previous and next random value
+    random: (f64, f64),
+    rng: StdRng,
+}
+
+impl Lfo {
+    pub fn new(shape: LfoShape, freq: f64) -> Self {
+        let mut rng = StdRng::from_entropy();
+        let random = (rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0));
+
+        let mut lfo = Self {
+            shape,
+            polarity: Polarity::Bipolar,
+            phase: 0.0,
+            step: 0.0,
+            offset: 0.0,
+            shown_phase: 0.0,
+            freq,
+            sync: None,
+            division: Division::new(1, 4),
+            retrigger: Trigger::new(),
+            fade_step: 1.0,
+            fade: 1.0,
+            random,
+            rng,
+        };
+
+        lfo.set_freq(freq);
+        lfo
+    }
+
+    pub fn with_polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    /// Sync to `bpm`, one cycle per `division`
+    pub fn synced(mut self, bpm: f64, division: Division) -> Self {
+        self.division = division;
+        self.set_bpm(bpm);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.set_seed(seed);
+        self
+    }
+
+    pub fn with_fade_in(mut self, time: f64) -> Self {
+        self.set_fade_in(time);
+        self.fade = 0.0;
+        self
+    }
+
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    pub fn set_freq(&mut self, freq: f64) {
+        self.freq = freq;
+        self.sync = None;
+        self.update_step();
+    }
+
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.sync = Some((bpm, self.division));
+        self.update_step();
+    }
+
+    pub fn set_division(&mut self, division: Division) {
+        self.division = division;
+        if let Some((bpm, _)) = self.sync {
+            self.sync = Some((bpm, division));
+            self.update_step();
+        }
+    }
+
+    /// Phase offset as a fraction of a cycle
+    pub fn set_phase_offset(&mut self, offset: f64) {
+        self.offset = clamp(offset, 0.0, 1.0);
+    }
+
+    /// Time in seconds to fade in after a retrigger
+    pub fn set_fade_in(&mut self, time: f64) {
+        self.fade_step = if time > 0.0 {
+            1.0 / (time * SAMPLE_RATE)
+        } else {
+            1.0
+        };
+    }
+
+    pub fn retrigger(&mut self) {
+        self.phase = 0.0;
+        self.fade = if self.fade_step < 1.0 { 0.0 } else { 1.0 };
+    }
+
+    fn update_step(&mut self) {
+        let freq = match self.sync {
+            Some((bpm, division)) => division.freq(bpm),
+            None => self.freq,
+        };
+        self.step = freq / SAMPLE_RATE;
+    }
+
+    fn next_random(&mut self) {
+        self.random = (self.random.1, self.rng.gen_range(-1.0, 1.0));
+    }
+
+    /// Value of the current shape in -1..1
+    fn shape_value(&self, phase: f64) -> f64 {
+        match self.shape {
+            LfoShape::Sine => (phase * TWOPI).sin(),
+            LfoShape::Triangle => 1.0 - (phase * 4.0 - 2.0).abs(),
+            LfoShape::SawUp => phase * 2.0 - 1.0,
+            LfoShape::SawDown => 1.0 - phase * 2.0,
+            LfoShape::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            LfoShape::SampleAndHold => self.random.0,
+            LfoShape::SmoothRandom => {
+                let t = (1.0 - (phase * PI).cos()) * 0.5;
+                self.random.0 + (self.random.1 - self.random.0) * t
+            }
+        }
+    }
+}
+
+impl Module for Lfo {
+    fn get(&mut self) -> f64 {
+        self.phase += self.step;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+        }
+
+        if self.fade < 1.0 {
+            self.fade = (self.fade + self.fade_step).min(1.0);
+        }
+
+        let mut phase = self.phase + self.offset;
+        if phase >= 1.0 {
+            phase -= 1.0;
+        }
+
+        // The random shapes change at the start of the offset cycle
+        if phase < self.shown_phase {
+            self.next_random();
+        }
+        self.shown_phase = phase;
+
+        let value = self.shape_value(phase);
+        let value = match self.polarity {
+            Polarity::Bipolar => value,
+            Polarity::Unipolar => (value + 1.0) * 0.5,
+        };
+        value * self.fade
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.set_freq(val),
+            1 if self.retrigger.process(val) => self.retrigger(),
+            2 => self.set_phase_offset(val),
+            3 => self.set_bpm(val),
+            4 => self.set_division(Division::from_control(val)),
+            5 => self.set_fade_in(val),
+            _ => ()
+        }
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.random = (self.rng.gen_range(-1.0, 1.0), self.rng.gen_range(-1.0, 1.0));
+    }
+}
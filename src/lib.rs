@@ -3,6 +3,27 @@ pub mod util;
 pub mod alsa;
 pub mod modules;
 pub mod freeverb;
+pub mod reverb;
 pub mod karplus_strong;
+pub mod filters;
+pub mod biquad;
+pub mod delay;
+pub mod tempo;
+pub mod distortion;
+pub mod oversample;
+pub mod dynamics;
+pub mod trigger;
+pub mod lfo;
+pub mod sequencer;
+pub mod clock;
+pub mod automaton;
+pub mod life;
+pub mod guitar;
+pub mod pitch;
+pub mod scala;
+pub mod quantizer;
+pub mod random;
+pub mod rack;
+pub mod golden;
 
 pub const SAMPLE_RATE: f64 = 48000.0;
@@ -0,0 +1,395 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::modules::Module;
+use crate::trigger::{Pulse, Trigger};
+
+/// Birth and survival rule of a Life-like automaton
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    /// `birth[n]`: a dead cell with `n` living neighbours comes alive
+    pub birth: [bool; 9],
+    /// `survival[n]`: a living cell with `n` living neighbours stays alive
+    pub survival: [bool; 9],
+}
+
+impl Rule {
+    /// Conway's Game of Life, B3/S23
+    pub fn life() -> Self {
+        Self::parse("B3/S23").unwrap()
+    }
+
+    /// Parse a rule in B/S notation, e.g. "B36/S23" for HighLife
+    pub fn parse(rule: &str) -> Result<Self, String> {
+        let mut birth = [false; 9];
+        let mut survival = [false; 9];
+
+        for part in rule.split('/') {
+            let mut chars = part.chars();
+            let counts = match chars.next() {
+                Some('B') | Some('b') => &mut birth,
+                Some('S') | Some('s') => &mut survival,
+                _ => return Err(format!("Invalid rule part \"{}\" in \"{}\"", part, rule)),
+            };
+
+            for c in chars {
+                match c.to_digit(10) {
+                    Some(n) if n <= 8 => counts[n as usize] = true,
+                    _ => return Err(format!("Invalid neighbour count '{}' in \"{}\"", c, rule)),
+                }
+            }
+        }
+
+        Ok(Self { birth, survival })
+    }
+}
+
+/// Glider in its two shapes, all other phases
+/// are rotations or reflections of these
+const GLIDERS: [[[bool; 3]; 3]; 2] = [
+    [[false, true, false], [false, false, true], [true, true, true]],
+    [[true, false, true], [false, true, true], [false, true, false]],
+];
+
+/// All orientations of a 3x3 pattern
+fn orientations(pattern: [[bool; 3]; 3]) -> Vec<[[bool; 3]; 3]> {
+    let mut result = Vec::new();
+    let mut p = pattern;
+
+    for _ in 0..4 {
+        let mut mirrored = p;
+        for row in mirrored.iter_mut() {
+            row.reverse();
+        }
+        for q in [p, mirrored].iter() {
+            if !result.contains(q) {
+                result.push(*q);
+            }
+        }
+
+        // Rotate by 90 degrees
+        let mut rotated = [[false; 3]; 3];
+        for (y, row) in rotated.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = p[2 - x][y];
+            }
+        }
+        p = rotated;
+    }
+
+    result
+}
+
+/// Two-dimensional, Life-like cellular automaton
+/// on a torus (the edges wrap around)
+#[derive(Debug, Clone)]
+pub struct Life {
+    width: usize,
+    height: usize,
+    cells: Vec<bool>,
+    next: Vec<bool>,
+    rule: Rule,
+    generation: usize,
+}
+
+impl Life {
+    pub fn new(width: usize, height: usize, rule: Rule) -> Self {
+        assert!(width >= 3 && height >= 3, "Grid must be at least 3x3 cells");
+        Self {
+            width,
+            height,
+            cells: vec![false; width * height],
+            next: vec![false; width * height],
+            rule,
+            generation: 0,
+        }
+    }
+
+    /// Fill with living cells with a probability of `density`,
+    /// the same seed gives the same grid
+    pub fn randomize(&mut self, seed: u64, density: f64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for cell in self.cells.iter_mut() {
+            *cell = rng.gen::<f64>() < density;
+        }
+        self.generation = 0;
+    }
+
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = false;
+        }
+        self.generation = 0;
+    }
+
+    /// Place a pattern with its top left corner at `x`, `y`,
+    /// `#` or `O` are living cells, everything else is dead
+    pub fn insert(&mut self, x: usize, y: usize, pattern: &[&str]) {
+        for (dy, line) in pattern.iter().enumerate() {
+            for (dx, c) in line.chars().enumerate() {
+                self.set(x + dx, y + dy, c == '#' || c == 'O');
+            }
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        (y % self.height) * self.width + (x % self.width)
+    }
+
+    /// State of the cell at `x`, `y`, coordinates wrap around
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.cells[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        let i = self.index(x, y);
+        self.cells[i] = alive;
+    }
+
+    fn neighbours(&self, x: usize, y: usize) -> usize {
+        let mut count = 0;
+        for dy in 0..3 {
+            for dx in 0..3 {
+                if (dx != 1 || dy != 1)
+                    && self.get(x + self.width + dx - 1, y + self.height + dy - 1) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Advance by one generation
+    pub fn step(&mut self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let n = self.neighbours(x, y);
+                let alive = if self.get(x, y) {
+                    self.rule.survival[n]
+                } else {
+                    self.rule.birth[n]
+                };
+                self.next[y * self.width + x] = alive;
+            }
+        }
+
+        std::mem::swap(&mut self.cells, &mut self.next);
+        self.generation += 1;
+    }
+
+    /// Number of living cells
+    pub fn population(&self) -> usize {
+        self.cells.iter().filter(|c| **c).count()
+    }
+
+    /// Fraction of living cells in column `x`, 0..1
+    pub fn column_density(&self, x: usize) -> f64 {
+        let alive = (0..self.height).filter(|y| self.get(x, *y)).count();
+        alive as f64 / self.height as f64
+    }
+
+    /// Fraction of living cells in row `y`, 0..1
+    pub fn row_density(&self, y: usize) -> f64 {
+        let alive = (0..self.width).filter(|x| self.get(*x, y)).count();
+        alive as f64 / self.width as f64
+    }
+
+    /// Number of isolated gliders in any phase and direction
+    pub fn gliders(&self) -> usize {
+        let patterns: Vec<_> = GLIDERS.iter().flat_map(|g| orientations(*g)).collect();
+        let mut count = 0;
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                // 5x5 window around the 3x3 pattern at x + 1, y + 1,
+                // the border has to be empty
+                let window = |dx: usize, dy: usize| self.get(x + dx, y + dy);
+                let border_empty = (0..5).all(|i| {
+                    !window(i, 0) && !window(i, 4) && !window(0, i) && !window(4, i)
+                });
+                if !border_empty {
+                    continue;
+                }
+
+                let matches = patterns.iter().any(|p| {
+                    (0..3).all(|py| (0..3).all(|px| p[py][px] == window(px + 1, py + 1)))
+                });
+                if matches {
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+
+    /// Write the grid as a plain PGM (P2) image,
+    /// living cells are white
+    pub fn write_pgm<W: std::io::Write>(&self, out: &mut W) -> std::io::Result<()> {
+        writeln!(out, "P2")?;
+        writeln!(out, "{} {}", self.width, self.height)?;
+        writeln!(out, "255")?;
+        self.write_pgm_rows(out)
+    }
+
+    /// Write only the pixel rows of the grid,
+    /// used to append several generations to one image
+    pub fn write_pgm_rows<W: std::io::Write>(&self, out: &mut W) -> std::io::Result<()> {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                write!(out, "{} ", if self.get(x, y) { 255 } else { 0 })?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays a `Life` grid like a step sequencer.
+///
+/// Each clock moves a playhead to the next column,
+/// after the last column the grid advances by one generation.
+/// Rows are mapped to the frequencies of a scale, the bottom row is the lowest note.
+///
+/// Inputs:
+///
+/// 0. Clock
+/// 1. Reset, restores the initial grid
+///
+/// Outputs:
+///
+/// 0. Amplitude, the density of the current column
+/// 1. Pitch in Hz of the lowest living cell in the current column
+/// 2. Glider trigger, fires when a generation has more gliders than the one before
+/// 3. Population, the fraction of living cells in the grid
+/// 4. Gate of the bottom row, followed by the gates of all rows above it
+pub struct LifeModule {
+    life: Life,
+    initial: Life,
+    scale: Vec<f64>,
+    column: Option<usize>,
+    gliders: usize,
+    clock: Trigger,
+    reset: Trigger,
+    glider_pulse: Pulse,
+    glider_value: f64,
+    pitch: f64,
+}
+
+impl LifeModule {
+    /// `scale` holds one frequency per row,
+    /// it is repeated an octave higher if the grid has more rows
+    pub fn new(life: Life, scale: Vec<f64>) -> Self {
+        assert!(!scale.is_empty(), "Scale needs at least one frequency");
+        let gliders = life.gliders();
+        Self {
+            initial: life.clone(),
+            life,
+            scale,
+            column: None,
+            gliders,
+            clock: Trigger::new(),
+            reset: Trigger::new(),
+            glider_pulse: Pulse::new(),
+            glider_value: 0.0,
+            pitch: 0.0,
+        }
+    }
+
+    pub fn life(&self) -> &Life {
+        &self.life
+    }
+
+    /// Column under the playhead
+    pub fn column(&self) -> Option<usize> {
+        self.column
+    }
+
+    /// Frequency of row `y`, counted from the top
+    pub fn row_freq(&self, y: usize) -> f64 {
+        let degree = self.life.height() - 1 - y;
+        let octave = (degree / self.scale.len()) as i32;
+        self.scale[degree % self.scale.len()] * 2.0_f64.powi(octave)
+    }
+
+    pub fn reset(&mut self) {
+        self.life = self.initial.clone();
+        self.column = None;
+        self.gliders = self.life.gliders();
+    }
+
+    fn clock(&mut self) {
+        let column = match self.column {
+            Some(c) if c + 1 >= self.life.width() => {
+                self.life.step();
+                let gliders = self.life.gliders();
+                if gliders > self.gliders {
+                    self.glider_pulse.trigger();
+                }
+                self.gliders = gliders;
+                0
+            }
+            Some(c) => c + 1,
+            None => 0,
+        };
+        self.column = Some(column);
+
+        if let Some(y) = (0..self.life.height()).rev().find(|y| self.life.get(column, *y)) {
+            self.pitch = self.row_freq(y);
+        }
+    }
+}
+
+impl Module for LifeModule {
+    fn get(&mut self) -> f64 {
+        self.glider_value = self.glider_pulse.process();
+        match self.column {
+            Some(x) => self.life.column_density(x),
+            None => 0.0,
+        }
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 if self.clock.process(val) => self.clock(),
+            1 if self.reset.process(val) => self.reset(),
+            _ => ()
+        }
+    }
+
+    fn outputs(&self) -> usize {
+        4 + self.life.height()
+    }
+
+    fn get_output(&self, i: usize) -> f64 {
+        match i {
+            1 => self.pitch,
+            2 => self.glider_value,
+            3 => self.life.population() as f64 / (self.life.width() * self.life.height()) as f64,
+            i if i >= 4 && i - 4 < self.life.height() => {
+                let y = self.life.height() - 1 - (i - 4);
+                match self.column {
+                    Some(x) if self.clock.is_high() && self.life.get(x, y) => 1.0,
+                    _ => 0.0,
+                }
+            }
+            _ => 0.0
+        }
+    }
+}
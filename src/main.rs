@@ -9,152 +9,56 @@ use patchwork::modules::*;
 use patchwork::util::{clamp, clamp_audio};
 use patchwork::alsa::{open_audio_dev, open_midi_dev};
 use patchwork::freeverb::Freeverb;
+use patchwork::reverb::{Reverb, ReverbModule};
+use patchwork::filters::{StateVariableFilter, SvfMode};
+use patchwork::distortion::{Curve, Waveshaper};
+use patchwork::dynamics::MasterBus;
+use patchwork::pitch::{NoteToFreq, Tuning};
+use patchwork::rack::Rack;
+use patchwork::scala;
 use patchwork::SAMPLE_RATE;
 
 // Sample format
 type SF = i16;
 
-struct Rack {
-    modules: Vec<Box<Module>>,
-    buffer: Vec<f64>,
-    buffer_back: Vec<f64>,
-    output: Option<usize>,
-    // output_id -> module_id, input_id
-    patches: Vec<Vec<(usize, usize)>>,
-    midi_inputs: usize,
+/// Plays a rack through the master bus and records it to `capture.wav`
+struct Output {
+    rack: Rack,
     writer: hound::WavWriter<std::io::BufWriter<std::fs::File>>,
-    freeverb: Freeverb,
+    master: MasterBus,
     stored_sample: Option<i16>,
 }
 
-/// A takes control events as inputs
-/// and outputs a single `f64` signal.
-///
-/// It can contain multiple modules
-/// with `f64` inputs and outputs
-/// that can be connected through a shared bus.
-impl Rack {
-    pub fn new(midi_inputs: usize) -> Self {
-        let mut buffer = Vec::new();
-        let mut buffer_back = Vec::new();
-        let mut patches = Vec::new();
-
-        for _ in 0..midi_inputs {
-            buffer.push(0.0);
-            buffer_back.push(0.0);
-            patches.push(Vec::new());
-        }
-
+impl Output {
+    fn new(rack: Rack) -> Self {
         let spec = hound::WavSpec {
-            channels: 1,
+            channels: 2,
             sample_rate: SAMPLE_RATE as u32,
             bits_per_sample: 16,
             sample_format: hound::SampleFormat::Int,
         };
 
         let writer = hound::WavWriter::create("capture.wav", spec).unwrap();
-        let mut freeverb = Freeverb::new();
-        freeverb.set_room_size(0.4);
 
         Self {
-            modules: Vec::new(),
-            buffer,
-            buffer_back,
-            patches,
-            output: None,
-            midi_inputs,
+            rack,
             writer,
-            freeverb,
+            master: MasterBus::new(),
             stored_sample: None,
         }
     }
-
-    pub fn process_control(&mut self, param: u32, val: i32) {
-        let param = param as usize;
-        // Map value from 0..127 to 0.0...1.0
-        let val = val as f64 / 127.0;
-
-        // TODO: Change links to make this possible w/o buffer
-        if param < self.midi_inputs {
-            self.buffer[param] = val;
-            self.buffer_back[param] = val;
-            for (mod_id, input_id) in &self.patches[param] {
-                self.modules[*mod_id - self.midi_inputs].set_input(*input_id, val);
-            }
-        }
-    }
-
-    /// Get the current output of the rack
-    pub fn get(&mut self) -> f64 {
-        let offset = self.midi_inputs;
-
-        for i in 0..self.modules.len() {
-            self.buffer_back[i + offset] = self.modules[i].get();
-        }
-        for i in 0..self.buffer.len() {
-            let val = self.buffer_back[i];
-            if val != self.buffer[i] {
-                self.buffer[i] = val;
-                for (mod_id, input_id) in &self.patches[i] {
-                    self.modules[*mod_id - self.midi_inputs].set_input(*input_id, val);
-                }
-            }
-        }
-
-        if let Some(output) = self.output {
-            self.buffer[output]
-        } else {
-            0.0
-        }
-    }
-
-    pub fn fix_input(&mut self, i: usize, val: f64) {
-        self.buffer[i] = val;
-    }
-
-    pub fn set_output(&mut self, i: usize) {
-        self.output = Some(i);
-    }
-
-    pub fn register_module(&mut self, module: Box<Module>) -> usize {
-        let id = self.modules.len();
-
-        self.buffer.push(0.0);
-        self.buffer_back.push(0.0);
-        self.modules.push(module);
-        self.patches.push(Vec::new());
-
-        id + self.midi_inputs
-    }
-
-    // TODO: Prevent patching multiple outputs to one input
-    pub fn patch(&mut self, output: usize, input: (usize, usize)) {
-        self.patches[output].push(input);
-    }
 }
 
-impl Iterator for Rack { 
+impl Iterator for Output {
     type Item = SF;
     fn next(&mut self) -> Option<Self::Item> {
         use sample::Sample;
 
-        // Mono -> Stereo
+        // Interleave left and right channel
         if let Some(s) = self.stored_sample.take() { return Some(s) };
-        
-        let z = self.get().min(0.999).max(-0.999);
-        let (l, r) = self.freeverb.process((z, z));
-
-        // // Distortion effect
-        let l = if l > 0.0 {
-           1.0 - f64::exp(-l)
-        } else {
-           -1.0 + f64::exp(l)
-        };
-        let r = if r > 0.0 {
-           1.0 - f64::exp(-r)
-        } else {
-           -1.0 + f64::exp(r)
-        };
+
+        let output = self.rack.get();
+        let (l, r) = self.master.process(output);
 
         self.writer.write_sample(i16::from_sample(l)).unwrap();
         self.writer.write_sample(i16::from_sample(r)).unwrap();
@@ -167,13 +71,13 @@ impl Iterator for Rack {
 }
 
 
-fn write_samples(p: &alsa::PCM, mmap: &mut alsa::direct::pcm::MmapPlayback<SF>, rack: &mut Rack)
+fn write_samples(p: &alsa::PCM, mmap: &mut alsa::direct::pcm::MmapPlayback<SF>, output: &mut Output)
     -> Result<bool, Box<error::Error>> {
     use alsa::pcm::State;
 
     // Write samples to DMA area from iterator
     if mmap.avail() > 0 {
-        mmap.write(rack);
+        mmap.write(output);
     }
 
     match mmap.status().state() {
@@ -194,6 +98,10 @@ fn read_midi_event(input: &mut seq::Input, rack: &mut Rack) -> Result<bool, Box<
             let data: seq::EvCtrl = ev.get_data().unwrap();
             rack.process_control(data.param, data.value);
         },
+        seq::EventType::Pgmchange => {
+            let data: seq::EvCtrl = ev.get_data().unwrap();
+            rack.select_tuning(data.value as usize);
+        },
         _ => ()
     }
     Ok(true)
@@ -209,10 +117,18 @@ fn run() -> Result<(), Box<error::Error>> {
     // 256 Voices synth
     let mut rack = Rack::new(8);
 
-    let freq1 = rack.register_module(
-        Box::new(LinMap::new(55.0, 220.0))
-    );
-    rack.patch(1, (freq1, 0));
+    // Scala tunings for program change 1 and up, as "scale.scl" or "scale.scl:mapping.kbm"
+    for arg in std::env::args().skip(1) {
+        let mut files = arg.splitn(2, ':');
+        let scl = files.next().unwrap();
+        rack.add_tuning(scala::load(scl, files.next())?);
+    }
+
+    // Controls are 0..1, so this maps to notes C#3..A3
+    let note1 = rack.register_module(Box::new(LinMap::new(41.0, 57.0)));
+    rack.patch(1, (note1, 0));
+    let freq1 = rack.register_module(Box::new(NoteToFreq::new(Tuning::standard())));
+    rack.patch(note1, (freq1, 0));
 
     let freq2_f = rack.register_module(Box::new(LinMap::new(0.025, 1.0)));
     rack.patch(2, (freq2_f, 0));
@@ -259,12 +175,34 @@ fn run() -> Result<(), Box<error::Error>> {
     rack.patch(saw3, (mix, 2));
     rack.patch(saw4, (mix, 3));
 
+    // Controls are 0..1, so this maps to 0..8000 Hz
+    let cutoff = rack.register_module(Box::new(LinMap::new(-8000.0, 8000.0)));
+    rack.patch(5, (cutoff, 0));
+    let filter = rack.register_module(Box::new(
+        StateVariableFilter::new(SvfMode::Lowpass, 4000.0, 0.0)
+    ));
+    rack.patch(mix, (filter, 0));
+    rack.patch(cutoff, (filter, 1));
+    rack.patch(6, (filter, 2));
+
     let vol = rack.register_module(Box::new(
         Mult::new()
     ));
     rack.patch(0, (vol, 0));
-    rack.patch(mix, (vol, 1));
-    rack.set_output(vol);
+    rack.patch(filter, (vol, 1));
+
+    let distortion = rack.register_module(Box::new(
+        Waveshaper::new(Curve::Exponential).with_oversampling(2)
+    ));
+    rack.patch(vol, (distortion, 0));
+
+    let mut freeverb = Freeverb::new();
+    freeverb.set_room_size(0.4);
+    let reverb = rack.register_module(Box::new(ReverbModule::new(freeverb)));
+    rack.patch(distortion, (reverb, 0));
+    rack.patch(distortion, (reverb, 1));
+    rack.patch(7, (reverb, 2));
+    rack.set_output(reverb, reverb + 1);
 
     // Create an array of fds to poll.
     use alsa::PollDescriptors;
@@ -273,10 +211,11 @@ fn run() -> Result<(), Box<error::Error>> {
     
     // Let's use the fancy new "direct mode" for minimum overhead!
     let mut mmap = audio_dev.direct_mmap_playback::<SF>()?;
+    let mut output = Output::new(rack);
    
     loop {
-        if write_samples(&audio_dev, &mut mmap, &mut rack)? { continue; }
-        if read_midi_event(&mut midi_input, &mut rack)? { continue; }
+        if write_samples(&audio_dev, &mut mmap, &mut output)? { continue; }
+        if read_midi_event(&mut midi_input, &mut output.rack)? { continue; }
         // Nothing to do, let's sleep until woken up by the kernel.
         alsa::poll::poll(&mut fds, 100)?;
     }
@@ -11,6 +11,7 @@ use patchwork::modules::*;
 use patchwork::util::{clamp, clamp_audio};
 use patchwork::alsa::{SAMPLE_RATE, open_audio_dev, open_midi_dev};
 use patchwork::freeverb::Freeverb;
+use patchwork::reverb::Reverb;
 
 const TWOPI: f64 = std::f64::consts::PI * 2.0;
 
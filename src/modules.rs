@@ -1,11 +1,32 @@
 use crate::SAMPLE_RATE;
 use crate::util::clamp_audio;
+use crate::pitch::Tuning;
 
 const TWOPI: f64 = std::f64::consts::PI * 2.0;
 
 pub trait Module {
     fn get(&mut self) -> f64;
     fn set_input(&mut self, i: usize, val: f64);
+
+    /// Number of outputs,
+    /// output `0` is the value returned by `get`
+    fn outputs(&self) -> usize {
+        1
+    }
+
+    /// Value of the additional output `i`,
+    /// as computed by the last call to `get`
+    fn get_output(&self, _i: usize) -> f64 {
+        0.0
+    }
+
+    /// Switch the tuning used to turn MIDI notes into frequencies,
+    /// modules that don't play notes ignore it
+    fn set_tuning(&mut self, _tuning: &Tuning) {}
+
+    /// Reseed the random number generator,
+    /// modules without randomness ignore it
+    fn set_seed(&mut self, _seed: u64) {}
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,250 @@
+use crate::modules::Module;
+
+const PI: f64 = std::f64::consts::PI;
+
+/// Taps of the resampling filters per oversampling step
+const TAPS_PER_PHASE: usize = 16;
+
+/// Blackman windowed sinc lowpass for resampling by `factor`
+fn lowpass(factor: usize) -> Vec<f64> {
+    let n = factor * TAPS_PER_PHASE;
+    // Cutoff relative to the oversampled rate,
+    // a bit below the original nyquist frequency
+    let cutoff = 0.45 / factor as f64;
+    let center = (n - 1) as f64 / 2.0;
+
+    let mut taps: Vec<f64> = (0..n).map(|i| {
+        let t = i as f64 - center;
+        let sinc = if t == 0.0 {
+            2.0 * cutoff
+        } else {
+            (2.0 * PI * cutoff * t).sin() / (PI * t)
+        };
+        let w = 2.0 * PI * i as f64 / (n - 1) as f64;
+        let window = 0.42 - 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+        sinc * window
+    }).collect();
+
+    let sum: f64 = taps.iter().sum();
+    for t in taps.iter_mut() {
+        *t /= sum;
+    }
+    taps
+}
+
+/// Raises the sample rate by an integer factor
+/// (zero stuffing followed by a polyphase lowpass)
+#[derive(Debug, Clone)]
+pub struct Upsampler {
+    factor: usize,
+    taps: Vec<f64>,
+    // Most recent input first
+    history: Vec<f64>,
+}
+
+impl Upsampler {
+    pub fn new(factor: usize) -> Self {
+        assert!(factor >= 1, "Oversampling factor must be at least 1");
+        Self {
+            factor,
+            taps: lowpass(factor),
+            history: vec![0.0; TAPS_PER_PHASE],
+        }
+    }
+
+    /// Write `factor` samples at the higher rate to `output`
+    pub fn process(&mut self, input: f64, output: &mut [f64]) {
+        self.history.rotate_right(1);
+        self.history[0] = input;
+
+        for (phase, out) in output.iter_mut().enumerate().take(self.factor) {
+            let mut acc = 0.0;
+            for (j, x) in self.history.iter().enumerate() {
+                acc += self.taps[phase + j * self.factor] * x;
+            }
+            // Compensate for the energy lost by zero stuffing
+            *out = acc * self.factor as f64;
+        }
+    }
+
+    /// Group delay in samples at the higher rate
+    pub fn latency(&self) -> f64 {
+        (self.taps.len() - 1) as f64 / 2.0
+    }
+
+    pub fn reset(&mut self) {
+        for v in self.history.iter_mut() {
+            *v = 0.0;
+        }
+    }
+}
+
+/// Lowers the sample rate by an integer factor
+/// (lowpass followed by decimation)
+#[derive(Debug, Clone)]
+pub struct Downsampler {
+    factor: usize,
+    taps: Vec<f64>,
+    // Most recent input first
+    history: Vec<f64>,
+}
+
+impl Downsampler {
+    pub fn new(factor: usize) -> Self {
+        assert!(factor >= 1, "Oversampling factor must be at least 1");
+        let taps = lowpass(factor);
+        Self {
+            factor,
+            history: vec![0.0; taps.len()],
+            taps,
+        }
+    }
+
+    /// Consume `factor` samples at the higher rate
+    pub fn process(&mut self, input: &[f64]) -> f64 {
+        for x in input.iter().take(self.factor) {
+            self.history.rotate_right(1);
+            self.history[0] = *x;
+        }
+
+        self.taps.iter().zip(self.history.iter()).map(|(t, x)| t * x).sum()
+    }
+
+    /// Group delay in samples at the higher rate
+    pub fn latency(&self) -> f64 {
+        (self.taps.len() - 1) as f64 / 2.0
+    }
+
+    pub fn reset(&mut self) {
+        for v in self.history.iter_mut() {
+            *v = 0.0;
+        }
+    }
+}
+
+/// Runs a module at `factor` times the sample rate.
+///
+/// Input 0 is treated as the audio input and upsampled,
+/// all other inputs are passed through unchanged.
+/// Each output of the module is filtered and downsampled again.
+///
+/// The wrapped module still computes its coefficients from `SAMPLE_RATE`,
+/// so at the higher rate every frequency runs `factor` times too high
+/// and every time runs `factor` times too fast. This applies to parameters
+/// set before wrapping as well as to inputs, and only inputs are scaled here.
+/// For a lowpass at 1 kHz oversampled by 4, pass a quarter of the cutoff
+/// to the constructor and scale the cutoff input the same way:
+///
+/// ```
+/// use patchwork::biquad::{Biquad, BiquadKind};
+/// use patchwork::oversample::Oversampled;
+///
+/// let factor = 4;
+/// let scale = 1.0 / factor as f64;
+/// let filter = Biquad::new(BiquadKind::Lowpass, 1000.0 * scale, 0.707, 0.0);
+/// let filter = Oversampled::new(filter, factor).with_input_scale(1, scale);
+/// ```
+///
+/// Times, e.g. an envelope's attack, are multiplied by `factor` instead.
+pub struct Oversampled<M: Module> {
+    module: M,
+    factor: usize,
+    input: f64,
+    scales: Vec<(usize, f64)>,
+    upsampler: Upsampler,
+    downsamplers: Vec<Downsampler>,
+    // Upsampled input and the outputs at the higher rate
+    buffer: Vec<f64>,
+    output_buffers: Vec<Vec<f64>>,
+    outputs: Vec<f64>,
+}
+
+impl<M: Module> Oversampled<M> {
+    pub fn new(module: M, factor: usize) -> Self {
+        let outputs = module.outputs();
+        Self {
+            module,
+            factor,
+            input: 0.0,
+            scales: Vec::new(),
+            upsampler: Upsampler::new(factor),
+            downsamplers: (0..outputs).map(|_| Downsampler::new(factor)).collect(),
+            buffer: vec![0.0; factor],
+            output_buffers: vec![vec![0.0; factor]; outputs],
+            outputs: vec![0.0; outputs],
+        }
+    }
+
+    /// Multiply values sent to input `i` by `scale`
+    pub fn with_input_scale(mut self, i: usize, scale: f64) -> Self {
+        self.scales.push((i, scale));
+        self
+    }
+
+    pub fn module(&mut self) -> &mut M {
+        &mut self.module
+    }
+
+    /// Latency added by the resampling filters, in samples at the original rate
+    pub fn latency(&self) -> f64 {
+        if self.factor == 1 {
+            0.0
+        } else {
+            (self.upsampler.latency() + self.downsamplers[0].latency()) / self.factor as f64
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.upsampler.reset();
+        for down in self.downsamplers.iter_mut() {
+            down.reset();
+        }
+    }
+}
+
+impl<M: Module> Module for Oversampled<M> {
+    fn get(&mut self) -> f64 {
+        if self.factor == 1 {
+            self.module.set_input(0, self.input);
+            self.outputs[0] = self.module.get();
+            for o in 1..self.outputs.len() {
+                self.outputs[o] = self.module.get_output(o);
+            }
+            return self.outputs[0];
+        }
+
+        self.upsampler.process(self.input, &mut self.buffer);
+        for (k, x) in self.buffer.iter().enumerate() {
+            self.module.set_input(0, *x);
+            self.output_buffers[0][k] = self.module.get();
+            for o in 1..self.output_buffers.len() {
+                self.output_buffers[o][k] = self.module.get_output(o);
+            }
+        }
+
+        for (o, down) in self.downsamplers.iter_mut().enumerate() {
+            self.outputs[o] = down.process(&self.output_buffers[o]);
+        }
+        self.outputs[0]
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        if i == 0 {
+            self.input = val;
+        } else {
+            let scale = self.scales.iter()
+                .find(|(input, _)| *input == i)
+                .map(|(_, scale)| *scale)
+                .unwrap_or(1.0);
+            self.module.set_input(i, val * scale);
+        }
+    }
+
+    fn outputs(&self) -> usize {
+        self.outputs.len()
+    }
+
+    fn get_output(&self, i: usize) -> f64 {
+        self.outputs.get(i).cloned().unwrap_or(0.0)
+    }
+}
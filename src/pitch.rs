@@ -0,0 +1,449 @@
+use crate::modules::Module;
+use crate::util::select;
+
+/// MIDI note number of A4
+pub const A4: u8 = 69;
+/// Standard concert pitch of A4 in Hz
+pub const A4_FREQ: f64 = 440.0;
+
+const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// Frequency of a (possibly fractional) MIDI note
+/// in 12 tone equal temperament with A4 = 440 Hz
+pub fn midi_to_freq(note: f64) -> f64 {
+    A4_FREQ * 2.0_f64.powf((note - A4 as f64) / 12.0)
+}
+
+/// Inverse of `midi_to_freq`, the result is fractional
+pub fn freq_to_midi(freq: f64) -> f64 {
+    A4 as f64 + 12.0 * (freq / A4_FREQ).log2()
+}
+
+/// Name of a MIDI note, e.g. "C#4" for 61, C4 is middle C (60)
+pub fn note_name(note: u8) -> String {
+    format!("{}{}", NAMES[note as usize % 12], note as i32 / 12 - 1)
+}
+
+/// Parse a note name like "C4", "F#2", "Eb3" or "A-1" to a MIDI note number.
+/// Any number of `#` or `b` accidentals may follow the letter
+pub fn parse_note(name: &str) -> Result<u8, String> {
+    let mut chars = name.trim().chars().peekable();
+
+    let class: i32 = match chars.next().map(|c| c.to_ascii_uppercase()) {
+        Some('C') => 0,
+        Some('D') => 2,
+        Some('E') => 4,
+        Some('F') => 5,
+        Some('G') => 7,
+        Some('A') => 9,
+        Some('B') => 11,
+        _ => return Err(format!("Invalid note name \"{}\"", name)),
+    };
+
+    let mut accidentals = 0;
+    while let Some(c) = chars.peek() {
+        match c {
+            '#' => accidentals += 1,
+            'b' => accidentals -= 1,
+            _ => break,
+        }
+        chars.next();
+    }
+
+    let octave: String = chars.collect();
+    let octave: i32 = octave.parse()
+        .map_err(|_| format!("Invalid octave in note name \"{}\"", name))?;
+
+    let note = (octave + 1) * 12 + class + accidentals;
+    if (0..=127).contains(&note) {
+        Ok(note as u8)
+    } else {
+        Err(format!("Note \"{}\" is outside of the MIDI range", name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scale {
+    Major,
+    Minor,
+    HarmonicMinor,
+    MelodicMinor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+    WholeTone,
+    Chromatic,
+}
+
+impl Scale {
+    pub const ALL: [Scale; 14] = [
+        Scale::Major,
+        Scale::Minor,
+        Scale::HarmonicMinor,
+        Scale::MelodicMinor,
+        Scale::Dorian,
+        Scale::Phrygian,
+        Scale::Lydian,
+        Scale::Mixolydian,
+        Scale::Locrian,
+        Scale::MajorPentatonic,
+        Scale::MinorPentatonic,
+        Scale::Blues,
+        Scale::WholeTone,
+        Scale::Chromatic,
+    ];
+
+    /// Select a scale from `ALL` with a control value 0..1
+    pub fn from_control(val: f64) -> Self {
+        Self::ALL[select(val, Self::ALL.len())]
+    }
+
+    /// Semitones of each degree above the root
+    pub fn semitones(&self) -> &'static [u8] {
+        match self {
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Scale::MelodicMinor => &[0, 2, 3, 5, 7, 9, 11],
+            Scale::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Scale::Phrygian => &[0, 1, 3, 5, 7, 8, 10],
+            Scale::Lydian => &[0, 2, 4, 6, 7, 9, 11],
+            Scale::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            Scale::Locrian => &[0, 1, 3, 5, 6, 8, 10],
+            Scale::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Scale::Blues => &[0, 3, 5, 6, 7, 10],
+            Scale::WholeTone => &[0, 2, 4, 6, 8, 10],
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        }
+    }
+
+    /// MIDI note of `degree` above `root`,
+    /// degrees outside of one octave wrap into the next one
+    pub fn note(&self, root: u8, degree: i32) -> i32 {
+        let semitones = self.semitones();
+        let len = semitones.len() as i32;
+        let octave = degree.div_euclid(len);
+        root as i32 + octave * 12 + semitones[degree.rem_euclid(len) as usize] as i32
+    }
+
+    /// Note of the scale closest to the fractional MIDI note `pitch`,
+    /// the lower note wins if two are equally close
+    pub fn quantize(&self, root: u8, pitch: f64) -> i32 {
+        let root = root as i32 % 12;
+        let octave = ((pitch - root as f64) / 12.0).floor() as i32;
+
+        let mut nearest = root + octave * 12;
+        for o in (octave - 1)..=(octave + 1) {
+            for s in self.semitones() {
+                let note = root + o * 12 + *s as i32;
+                if (note as f64 - pitch).abs() < (nearest as f64 - pitch).abs() {
+                    nearest = note;
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Whether `note` belongs to the scale starting at `root`
+    pub fn contains(&self, root: u8, note: u8) -> bool {
+        let class = (note as i32 - root as i32).rem_euclid(12) as u8;
+        self.semitones().contains(&class)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChordType {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Major7,
+    Minor7,
+    Dominant7,
+    HalfDiminished7,
+    Diminished7,
+}
+
+impl ChordType {
+    /// Semitones of each chord tone above the root
+    pub fn intervals(&self) -> &'static [u8] {
+        match self {
+            ChordType::Major => &[0, 4, 7],
+            ChordType::Minor => &[0, 3, 7],
+            ChordType::Diminished => &[0, 3, 6],
+            ChordType::Augmented => &[0, 4, 8],
+            ChordType::Sus2 => &[0, 2, 7],
+            ChordType::Sus4 => &[0, 5, 7],
+            ChordType::Major7 => &[0, 4, 7, 11],
+            ChordType::Minor7 => &[0, 3, 7, 10],
+            ChordType::Dominant7 => &[0, 4, 7, 10],
+            ChordType::HalfDiminished7 => &[0, 3, 6, 10],
+            ChordType::Diminished7 => &[0, 3, 6, 9],
+        }
+    }
+
+    /// MIDI notes of the chord on `root`
+    pub fn notes(&self, root: u8) -> Vec<u8> {
+        self.intervals().iter().map(|i| root.saturating_add(*i)).collect()
+    }
+}
+
+/// Which scale degree each key plays, as in Scala keyboard mapping files.
+///
+/// The mapping repeats every `keys.len()` keys, transposed by `octave_degree`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    /// Scale degree of each key starting at the middle note,
+    /// `None` for keys that are not mapped.
+    /// An empty mapping maps every key to the next degree.
+    pub keys: Vec<Option<i32>>,
+    pub first_note: u8,
+    pub last_note: u8,
+    /// Key playing the first degree of the scale
+    pub middle_note: u8,
+    pub reference_note: u8,
+    pub reference_freq: f64,
+    /// Degree one repetition of the mapping is transposed by,
+    /// 0 uses the period of the scale
+    pub octave_degree: i32,
+}
+
+impl Default for KeyboardMapping {
+    /// Every key plays the next degree, the first degree is on C4 and A4 = 440 Hz
+    fn default() -> Self {
+        Self {
+            keys: Vec::new(),
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: A4,
+            reference_freq: A4_FREQ,
+            octave_degree: 0,
+        }
+    }
+}
+
+/// Maps MIDI notes to frequencies.
+///
+/// A tuning is a list of scale steps in cents above the first degree,
+/// the last step is the period the scale repeats at (usually an octave).
+/// The keyboard mapping decides which key plays which degree
+/// and sets the absolute pitch with a reference note, e.g. A4 = 440 Hz.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuning {
+    steps: Vec<f64>,
+    mapping: KeyboardMapping,
+}
+
+impl Tuning {
+    /// Tuning from the cents of each step above the first degree,
+    /// the last value is the period
+    pub fn from_cents(steps: Vec<f64>) -> Self {
+        assert!(!steps.is_empty(), "A tuning needs at least one step");
+        Self { steps, mapping: KeyboardMapping::default() }
+    }
+
+    /// Tuning from frequency ratios, e.g. 3/2 for a just fifth
+    pub fn from_ratios(ratios: &[f64]) -> Self {
+        Self::from_cents(ratios.iter().map(|r| 1200.0 * r.log2()).collect())
+    }
+
+    /// Octave divided into `divisions` equal steps
+    pub fn equal(divisions: usize) -> Self {
+        let step = 1200.0 / divisions as f64;
+        Self::from_cents((1..=divisions).map(|i| i as f64 * step).collect())
+    }
+
+    /// 12 tone equal temperament with A4 = 440 Hz
+    pub fn standard() -> Self {
+        Self::equal(12)
+    }
+
+    /// `note` sounds at `freq` Hz
+    pub fn with_reference(mut self, note: u8, freq: f64) -> Self {
+        self.mapping.reference_note = note;
+        self.mapping.reference_freq = freq;
+        self
+    }
+
+    /// `note` plays the first degree of the scale
+    pub fn with_base_note(mut self, note: u8) -> Self {
+        self.mapping.middle_note = note;
+        self
+    }
+
+    pub fn with_mapping(mut self, mapping: KeyboardMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    pub fn mapping(&self) -> &KeyboardMapping {
+        &self.mapping
+    }
+
+    /// Cents of each step above the first degree
+    pub fn steps(&self) -> &[f64] {
+        &self.steps
+    }
+
+    /// Number of steps per period
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Size of the period in cents, 1200 for an octave
+    pub fn period(&self) -> f64 {
+        self.steps[self.steps.len() - 1]
+    }
+
+    /// Cents of `degree` above the first degree
+    fn degree_cents(&self, degree: i32) -> f64 {
+        let len = self.steps.len() as i32;
+        let period = degree.div_euclid(len) as f64;
+        let step = degree.rem_euclid(len);
+        let cents = if step == 0 { 0.0 } else { self.steps[step as usize - 1] };
+        period * self.period() + cents
+    }
+
+    /// Cents of `note` above the middle note, `None` if the key is not mapped
+    fn mapped_cents(&self, note: i32) -> Option<f64> {
+        let mapping = &self.mapping;
+        if note < mapping.first_note as i32 || note > mapping.last_note as i32 {
+            return None;
+        }
+
+        let offset = note - mapping.middle_note as i32;
+        if mapping.keys.is_empty() {
+            return Some(self.degree_cents(offset));
+        }
+
+        let len = mapping.keys.len() as i32;
+        let octave = if mapping.octave_degree > 0 {
+            self.degree_cents(mapping.octave_degree)
+        } else {
+            self.period()
+        };
+        mapping.keys[offset.rem_euclid(len) as usize]
+            .map(|degree| self.degree_cents(degree) + offset.div_euclid(len) as f64 * octave)
+    }
+
+    /// Whether `note` plays a degree of the scale
+    pub fn is_mapped(&self, note: u8) -> bool {
+        self.mapped_cents(note as i32).is_some()
+    }
+
+    /// Cents of `note` above the middle note,
+    /// keys that are not mapped sound like the closest mapped key
+    fn key_cents(&self, note: i32) -> f64 {
+        let mapping = &self.mapping;
+        let first = mapping.first_note as i32;
+        let last = mapping.last_note as i32;
+        let note = note.max(first).min(last);
+
+        for distance in 0..=(last - first) {
+            let cents = self.mapped_cents(note - distance)
+                .or_else(|| self.mapped_cents(note + distance));
+            if let Some(cents) = cents {
+                return cents;
+            }
+        }
+        0.0
+    }
+
+    /// Cents of a fractional note above the middle note,
+    /// in between two keys the pitch is interpolated linearly in cents
+    fn cents(&self, note: f64) -> f64 {
+        let whole = note.floor();
+        let low = self.key_cents(whole as i32);
+        let high = self.key_cents(whole as i32 + 1);
+        low + (high - low) * (note - whole)
+    }
+
+    /// Frequency of a (possibly fractional) MIDI note
+    pub fn freq(&self, note: f64) -> f64 {
+        let cents = self.cents(note) - self.key_cents(self.mapping.reference_note as i32);
+        self.mapping.reference_freq * 2.0_f64.powf(cents / 1200.0)
+    }
+
+    /// Fractional MIDI note sounding at `freq`, the inverse of `freq`
+    pub fn note(&self, freq: f64) -> f64 {
+        let first = self.mapping.first_note as i32;
+        let last = self.mapping.last_note as i32;
+        let cents = 1200.0 * (freq / self.mapping.reference_freq).log2()
+            + self.key_cents(self.mapping.reference_note as i32);
+
+        // Start with the average step size and walk to the right key
+        let average = self.period() / self.steps.len() as f64;
+        let mut note = (self.mapping.middle_note as i32 + (cents / average).floor() as i32)
+            .max(first)
+            .min(last);
+        while note > first && self.key_cents(note) > cents {
+            note -= 1;
+        }
+        while note < last && self.key_cents(note + 1) <= cents {
+            note += 1;
+        }
+
+        let low = self.key_cents(note);
+        let high = self.key_cents(note + 1);
+        if high > low {
+            note as f64 + ((cents - low) / (high - low)).max(0.0)
+        } else {
+            note as f64
+        }
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+/// Converts MIDI note numbers to frequencies with a `Tuning`
+///
+/// Inputs:
+///
+/// 0. Note, fractional MIDI note number
+pub struct NoteToFreq {
+    tuning: Tuning,
+    note: f64,
+    freq: f64,
+}
+
+impl NoteToFreq {
+    pub fn new(tuning: Tuning) -> Self {
+        let note = A4 as f64;
+        Self { freq: tuning.freq(note), tuning, note }
+    }
+}
+
+impl Module for NoteToFreq {
+    fn get(&mut self) -> f64 {
+        self.freq
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        if i == 0 {
+            self.note = val;
+            self.freq = self.tuning.freq(val);
+        }
+    }
+
+    fn set_tuning(&mut self, tuning: &Tuning) {
+        self.tuning = tuning.clone();
+        self.freq = self.tuning.freq(self.note);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::modules::Module;
+use crate::pitch::{Scale, Tuning};
+use crate::trigger::Pulse;
+
+/// Snaps a continuous pitch to the nearest note of a scale.
+///
+/// Inputs:
+///
+/// 0. Pitch, fractional MIDI note
+/// 1. Scale, control value 0..1, see `Scale::from_control`
+/// 2. Root, pitch class 0..11 starting at C
+///
+/// Outputs:
+///
+/// 0. Quantized MIDI note
+/// 1. Frequency of the quantized note in Hz
+/// 2. Trigger when the quantized note changes
+pub struct Quantizer {
+    scale: Scale,
+    root: u8,
+    pitch: f64,
+    note: Option<i32>,
+    freq: f64,
+    tuning: Tuning,
+    changed: Pulse,
+    trigger: f64,
+}
+
+impl Quantizer {
+    pub fn new(scale: Scale, root: u8) -> Self {
+        Self {
+            scale,
+            root: root % 12,
+            pitch: 0.0,
+            note: None,
+            freq: 0.0,
+            tuning: Tuning::standard(),
+            changed: Pulse::new(),
+            trigger: 0.0,
+        }
+    }
+
+    pub fn with_tuning(mut self, tuning: Tuning) -> Self {
+        self.tuning = tuning;
+        self
+    }
+
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.scale = scale;
+        self.update();
+    }
+
+    /// Pitch class of the first degree, 0 is C
+    pub fn set_root(&mut self, root: u8) {
+        self.root = root % 12;
+        self.update();
+    }
+
+    /// Current quantized MIDI note
+    pub fn note(&self) -> Option<i32> {
+        self.note
+    }
+
+    fn update(&mut self) {
+        let note = self.scale.quantize(self.root, self.pitch);
+        if self.note != Some(note) {
+            self.note = Some(note);
+            self.freq = self.tuning.freq(note as f64);
+            self.changed.trigger();
+        }
+    }
+}
+
+impl Module for Quantizer {
+    fn get(&mut self) -> f64 {
+        self.trigger = self.changed.process();
+        self.note.unwrap_or(0) as f64
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => {
+                self.pitch = val;
+                self.update();
+            }
+            1 => self.set_scale(Scale::from_control(val)),
+            2 => self.set_root(val.round().rem_euclid(12.0) as u8),
+            _ => ()
+        }
+    }
+
+    fn outputs(&self) -> usize {
+        3
+    }
+
+    fn get_output(&self, i: usize) -> f64 {
+        match i {
+            0 => self.note.unwrap_or(0) as f64,
+            1 => self.freq,
+            2 => self.trigger,
+            _ => 0.0
+        }
+    }
+
+    fn set_tuning(&mut self, tuning: &Tuning) {
+        self.tuning = tuning.clone();
+        if let Some(note) = self.note {
+            self.freq = self.tuning.freq(note as f64);
+        }
+    }
+}
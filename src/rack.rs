@@ -0,0 +1,147 @@
+use crate::modules::Module;
+use crate::pitch::Tuning;
+use crate::random::RngSource;
+
+pub struct Rack {
+    modules: Vec<Box<dyn Module>>,
+    // module_id -> id of its first output
+    slots: Vec<usize>,
+    buffer: Vec<f64>,
+    buffer_back: Vec<f64>,
+    // left, right
+    output: Option<(usize, usize)>,
+    // output_id -> module index, input_id
+    patches: Vec<Vec<(usize, usize)>>,
+    midi_inputs: usize,
+    // Tunings selected by program change
+    tunings: Vec<Tuning>,
+    // Seeds for the modules
+    rng: RngSource,
+}
+
+/// A takes control events as inputs
+/// and outputs a stereo pair of `f64` signals.
+///
+/// It can contain multiple modules
+/// with `f64` inputs and outputs
+/// that can be connected through a shared bus.
+impl Rack {
+    pub fn new(midi_inputs: usize) -> Self {
+        let mut buffer = Vec::new();
+        let mut buffer_back = Vec::new();
+        let mut patches = Vec::new();
+
+        for _ in 0..midi_inputs {
+            buffer.push(0.0);
+            buffer_back.push(0.0);
+            patches.push(Vec::new());
+        }
+
+        Self {
+            modules: Vec::new(),
+            slots: Vec::new(),
+            buffer,
+            buffer_back,
+            patches,
+            output: None,
+            midi_inputs,
+            tunings: vec![Tuning::standard()],
+            rng: RngSource::from_entropy(),
+        }
+    }
+
+    /// Seed for the modules registered afterwards,
+    /// the same seed and patch render the same output
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = RngSource::new(seed);
+        self
+    }
+
+    pub fn process_control(&mut self, param: u32, val: i32) {
+        let param = param as usize;
+        // Map value from 0..127 to 0.0...1.0
+        let val = val as f64 / 127.0;
+
+        // TODO: Change links to make this possible w/o buffer
+        if param < self.midi_inputs {
+            self.buffer[param] = val;
+            self.buffer_back[param] = val;
+            for (mod_id, input_id) in &self.patches[param] {
+                self.modules[*mod_id].set_input(*input_id, val);
+            }
+        }
+    }
+
+    /// Tunings selectable by program change,
+    /// program 0 is 12 tone equal temperament
+    pub fn add_tuning(&mut self, tuning: Tuning) {
+        self.tunings.push(tuning);
+    }
+
+    pub fn select_tuning(&mut self, program: usize) {
+        if let Some(tuning) = self.tunings.get(program) {
+            for module in self.modules.iter_mut() {
+                module.set_tuning(tuning);
+            }
+        }
+    }
+
+    /// Get the current (left, right) output of the rack
+    pub fn get(&mut self) -> (f64, f64) {
+        for (module, slot) in self.modules.iter_mut().zip(self.slots.iter()) {
+            self.buffer_back[*slot] = module.get();
+            for o in 1..module.outputs() {
+                self.buffer_back[*slot + o] = module.get_output(o);
+            }
+        }
+        for i in 0..self.buffer.len() {
+            let val = self.buffer_back[i];
+            if val != self.buffer[i] {
+                self.buffer[i] = val;
+                for (mod_id, input_id) in &self.patches[i] {
+                    self.modules[*mod_id].set_input(*input_id, val);
+                }
+            }
+        }
+
+        if let Some((l, r)) = self.output {
+            (self.buffer[l], self.buffer[r])
+        } else {
+            (0.0, 0.0)
+        }
+    }
+
+    pub fn fix_input(&mut self, i: usize, val: f64) {
+        self.buffer[i] = val;
+    }
+
+    /// Use the same output for both channels for mono patches
+    pub fn set_output(&mut self, l: usize, r: usize) {
+        self.output = Some((l, r));
+    }
+
+    /// Returns the id of the first output of the module,
+    /// additional outputs follow directly after it.
+    pub fn register_module(&mut self, mut module: Box<dyn Module>) -> usize {
+        let id = self.buffer.len();
+        module.set_seed(self.rng.next_seed());
+
+        for _ in 0..module.outputs() {
+            self.buffer.push(0.0);
+            self.buffer_back.push(0.0);
+            self.patches.push(Vec::new());
+        }
+        self.slots.push(id);
+        self.modules.push(module);
+
+        id
+    }
+
+    // TODO: Prevent patching multiple outputs to one input
+    pub fn patch(&mut self, output: usize, input: (usize, usize)) {
+        let (id, input_id) = input;
+        let module = self.slots.iter().position(|s| *s == id)
+            .expect("Patch target is not the id of a module");
+        self.patches[output].push((module, input_id));
+    }
+}
@@ -0,0 +1,63 @@
+//! Seedable randomness for stochastic modules.
+//!
+//! Every module with randomness owns a `StdRng` and can be reseeded
+//! with `Module::set_seed`. A rack hands out seeds from one `RngSource`,
+//! so the same rack seed renders the same output every time.
+
+use rand::{FromEntropy, Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+/// Generates seeds for the modules of a rack
+#[derive(Debug, Clone)]
+pub struct RngSource {
+    rng: StdRng,
+}
+
+impl RngSource {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: StdRng::seed_from_u64(seed) }
+    }
+
+    /// Different seeds on every run
+    pub fn from_entropy() -> Self {
+        Self { rng: StdRng::from_entropy() }
+    }
+
+    /// Seeded with the value after `--seed` on the command line,
+    /// from entropy if it is missing. Used by the offline renders,
+    /// exits with a usage error if the seed is not a number
+    pub fn from_args() -> Self {
+        match seed_arg() {
+            Ok(Some(seed)) => Self::new(seed),
+            Ok(None) => Self::from_entropy(),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    pub fn next_seed(&mut self) -> u64 {
+        self.rng.gen()
+    }
+
+    /// Generator with the next seed
+    pub fn rng(&mut self) -> StdRng {
+        StdRng::seed_from_u64(self.next_seed())
+    }
+}
+
+/// Value of the `--seed` command line option, `None` if it is not given
+pub fn seed_arg() -> Result<Option<u64>, String> {
+    let mut args = std::env::args().skip_while(|a| a != "--seed");
+    if args.next().is_none() {
+        return Ok(None);
+    }
+
+    match args.next() {
+        Some(seed) => seed.parse()
+            .map(Some)
+            .map_err(|_| format!("--seed needs an unsigned integer, got \"{}\"", seed)),
+        None => Err("--seed needs an unsigned integer".to_string()),
+    }
+}
@@ -0,0 +1,273 @@
+use crate::SAMPLE_RATE;
+use crate::delay::{DelayLine, Interpolation};
+use crate::modules::Phase;
+use crate::util::clamp;
+use super::{Reverb, wet_gains, scale_length};
+
+const TWOPI: f64 = std::f64::consts::PI * 2.0;
+
+const LINES: usize = 8;
+/// Mutually prime lengths in samples at 48kHz
+const LINE_TUNING: [usize; LINES] = [1433, 1601, 1867, 2053, 2251, 2399, 2617, 2903];
+/// Rates of the delay time modulation in Hz
+const MOD_RATES: [f64; LINES] = [0.31, 0.37, 0.43, 0.53, 0.61, 0.71, 0.79, 0.89];
+const INPUT_GAIN: f64 = 0.35;
+const OUTPUT_GAIN: f64 = 0.5;
+const MIN_T60: f64 = 0.2;
+const MAX_T60: f64 = 12.0;
+
+/// First order lowpass with separate gains at DC and nyquist,
+/// (Jot's absorption filter)
+/// used to make high frequencies decay faster than low ones.
+#[derive(Debug, Clone)]
+struct DecayFilter {
+    gain: f64,
+    pole: f64,
+    state: f64,
+}
+
+impl DecayFilter {
+    fn new() -> Self {
+        Self { gain: 0.0, pole: 0.0, state: 0.0 }
+    }
+
+    /// Set the gains so a signal passing the filter once per `delay` seconds
+    /// decays by 60dB in `t60_low` seconds at DC and `t60_high` seconds at nyquist
+    fn set_decay(&mut self, delay: f64, t60_low: f64, t60_high: f64) {
+        let g_low = 10.0_f64.powf(-3.0 * delay / t60_low);
+        let g_high = 10.0_f64.powf(-3.0 * delay / t60_high);
+        self.gain = g_low;
+        self.pole = (g_low - g_high) / (g_low + g_high);
+    }
+
+    fn set_lossless(&mut self) {
+        self.gain = 1.0;
+        self.pole = 0.0;
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        self.state = self.gain * (1.0 - self.pole) * input + self.pole * self.state;
+        self.state
+    }
+}
+
+/// In-place fast Walsh-Hadamard transform, normalized so it is orthogonal
+fn hadamard(values: &mut [f64; LINES]) {
+    let mut h = 1;
+    while h < LINES {
+        for i in (0..LINES).step_by(h * 2) {
+            for j in i..(i + h) {
+                let a = values[j];
+                let b = values[j + h];
+                values[j] = a + b;
+                values[j + h] = a - b;
+            }
+        }
+        h *= 2;
+    }
+
+    let norm = 1.0 / (LINES as f64).sqrt();
+    for v in values.iter_mut() {
+        *v *= norm;
+    }
+}
+
+/// Feedback delay network with eight modulated delay lines
+/// mixed by a hadamard matrix.
+///
+/// The decay time is set separately for low (`t60_low`) and
+/// high (`t60_high`) frequencies.
+/// For the shared `Reverb` interface,
+/// room size maps to the low frequency decay time
+/// and dampening shortens the high frequency decay relative to it.
+pub struct Fdn {
+    lines: Vec<DelayLine>,
+    filters: Vec<DecayFilter>,
+    lfos: Vec<Phase>,
+    /// Length of each line in samples, without modulation
+    lengths: [f64; LINES],
+    mod_depth: f64,
+    t60_low: f64,
+    t60_high: f64,
+    input_gain: f64,
+    wet_gains: (f64, f64),
+    wet: f64,
+    dry: f64,
+    width: f64,
+    dampening: f64,
+    room_size: f64,
+    frozen: bool,
+}
+
+impl Fdn {
+    pub fn new() -> Self {
+        let mod_depth = 12.0;
+
+        let mut lengths = [0.0; LINES];
+        let mut lines = Vec::with_capacity(LINES);
+        let mut lfos = Vec::with_capacity(LINES);
+        for i in 0..LINES {
+            let length = scale_length(LINE_TUNING[i], 48000.0);
+            lengths[i] = length as f64;
+            lines.push(DelayLine::new(length + mod_depth as usize + 2, Interpolation::Linear));
+            lfos.push(Phase::new(MOD_RATES[i]));
+        }
+
+        let mut fdn = Self {
+            lines,
+            filters: vec![DecayFilter::new(); LINES],
+            lfos,
+            lengths,
+            mod_depth,
+            t60_low: 2.0,
+            t60_high: 1.0,
+            input_gain: INPUT_GAIN,
+            wet_gains: (0.0, 0.0),
+            wet: 0.0,
+            dry: 0.0,
+            width: 0.0,
+            dampening: 0.0,
+            room_size: 0.0,
+            frozen: false,
+        };
+
+        fdn.set_wet(1.0);
+        fdn.set_width(0.5);
+        fdn.set_dampening(0.5);
+        fdn.set_room_size(0.5);
+
+        fdn
+    }
+
+    /// Set the decay times in seconds directly,
+    /// overriding room size and dampening
+    pub fn set_decay(&mut self, t60_low: f64, t60_high: f64) {
+        self.t60_low = clamp(t60_low, MIN_T60, MAX_T60);
+        self.t60_high = clamp(t60_high, MIN_T60, MAX_T60);
+        self.update_filters();
+    }
+
+    pub fn decay(&self) -> (f64, f64) {
+        (self.t60_low, self.t60_high)
+    }
+
+    /// Depth of the delay time modulation in samples
+    pub fn set_modulation_depth(&mut self, depth: f64) {
+        self.mod_depth = clamp(depth, 0.0, 12.0);
+    }
+
+    fn update_from_parameters(&mut self) {
+        let t60_low = MIN_T60 + self.room_size * self.room_size * (MAX_T60 - MIN_T60);
+        let t60_high = t60_low * (1.0 - 0.9 * self.dampening);
+        self.set_decay(t60_low, t60_high);
+    }
+
+    fn update_filters(&mut self) {
+        for (filter, length) in self.filters.iter_mut().zip(self.lengths.iter()) {
+            if self.frozen {
+                filter.set_lossless();
+            } else {
+                filter.set_decay(length / SAMPLE_RATE, self.t60_low, self.t60_high);
+            }
+        }
+        self.input_gain = if self.frozen { 0.0 } else { INPUT_GAIN };
+    }
+}
+
+impl Default for Fdn {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reverb for Fdn {
+    fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        let input_mixed = (input.0 + input.1) * 0.5 * self.input_gain;
+
+        let mut values = [0.0; LINES];
+        for (i, value) in values.iter_mut().enumerate() {
+            let modulation = (self.lfos[i].get() * TWOPI).sin() * 0.5 + 0.5;
+            let delay = self.lengths[i] + modulation * self.mod_depth;
+            *value = self.filters[i].process(self.lines[i].read(delay));
+        }
+
+        // Even lines go to the left, odd lines to the right channel
+        let mut out = (0.0, 0.0);
+        for pair in values.chunks(2) {
+            out.0 += pair[0] * OUTPUT_GAIN;
+            out.1 += pair[1] * OUTPUT_GAIN;
+        }
+
+        hadamard(&mut values);
+        // Alternate the sign of the input to decorrelate the lines
+        for (lines, values) in self.lines.chunks_mut(2).zip(values.chunks(2)) {
+            lines[0].write(values[0] + input_mixed);
+            lines[1].write(values[1] - input_mixed);
+        }
+
+        (out.0 * self.wet_gains.0 + out.1 * self.wet_gains.1 + input.0 * self.dry,
+         out.1 * self.wet_gains.0 + out.0 * self.wet_gains.1 + input.1 * self.dry)
+    }
+
+    fn set_room_size(&mut self, value: f64) {
+        self.room_size = value;
+        self.update_from_parameters();
+    }
+
+    fn room_size(&self) -> f64 {
+        self.room_size
+    }
+
+    fn set_dampening(&mut self, value: f64) {
+        self.dampening = value;
+        self.update_from_parameters();
+    }
+
+    fn dampening(&self) -> f64 {
+        self.dampening
+    }
+
+    fn set_wet(&mut self, value: f64) {
+        self.wet = value;
+        self.wet_gains = wet_gains(self.wet, self.width);
+    }
+
+    fn wet(&self) -> f64 {
+        self.wet
+    }
+
+    fn set_dry(&mut self, value: f64) {
+        self.dry = value;
+    }
+
+    fn dry(&self) -> f64 {
+        self.dry
+    }
+
+    fn set_width(&mut self, value: f64) {
+        self.width = value;
+        self.wet_gains = wet_gains(self.wet, self.width);
+    }
+
+    fn width(&self) -> f64 {
+        self.width
+    }
+
+    fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+        self.update_filters();
+    }
+
+    fn frozen(&self) -> bool {
+        self.frozen
+    }
+
+    fn reset(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.reset();
+        }
+        for filter in self.filters.iter_mut() {
+            filter.state = 0.0;
+        }
+    }
+}
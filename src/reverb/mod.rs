@@ -0,0 +1,112 @@
+use crate::modules::Module;
+
+pub mod fdn;
+pub mod plate;
+
+/// Parameter interface shared by all reverb algorithms,
+/// so patches can swap one for another.
+///
+/// All parameters are in the range 0..1.
+pub trait Reverb {
+    fn process(&mut self, input: (f64, f64)) -> (f64, f64);
+
+    fn set_room_size(&mut self, value: f64);
+    fn room_size(&self) -> f64;
+
+    fn set_dampening(&mut self, value: f64);
+    fn dampening(&self) -> f64;
+
+    fn set_wet(&mut self, value: f64);
+    fn wet(&self) -> f64;
+
+    fn set_dry(&mut self, value: f64);
+    fn dry(&self) -> f64;
+
+    fn set_width(&mut self, value: f64);
+    fn width(&self) -> f64;
+
+    /// Mute the input and hold the current tail forever
+    fn set_frozen(&mut self, frozen: bool);
+    fn frozen(&self) -> bool;
+
+    /// Clear all delay lines
+    fn reset(&mut self);
+}
+
+/// Gains for the (same, opposite) channel of the wet signal
+pub fn wet_gains(wet: f64, width: f64) -> (f64, f64) {
+    (
+        wet * ((1.0 + width) / 2.0),
+        wet * ((1.0 - width) / 2.0),
+    )
+}
+
+/// Exposes any reverb as a stereo rack module
+///
+/// Inputs:
+///
+/// 0. Left signal
+/// 1. Right signal
+/// 2. Room size
+/// 3. Dampening
+/// 4. Wet
+/// 5. Dry
+/// 6. Width
+/// 7. Freeze, frozen while > 0.5
+///
+/// Outputs:
+///
+/// 0. Left
+/// 1. Right
+pub struct ReverbModule<R: Reverb> {
+    reverb: R,
+    input: (f64, f64),
+    output: (f64, f64),
+}
+
+impl<R: Reverb> ReverbModule<R> {
+    pub fn new(reverb: R) -> Self {
+        Self { reverb, input: (0.0, 0.0), output: (0.0, 0.0) }
+    }
+
+    pub fn reverb(&mut self) -> &mut R {
+        &mut self.reverb
+    }
+}
+
+impl<R: Reverb> Module for ReverbModule<R> {
+    fn get(&mut self) -> f64 {
+        self.output = self.reverb.process(self.input);
+        self.output.0
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.input.0 = val,
+            1 => self.input.1 = val,
+            2 => self.reverb.set_room_size(val),
+            3 => self.reverb.set_dampening(val),
+            4 => self.reverb.set_wet(val),
+            5 => self.reverb.set_dry(val),
+            6 => self.reverb.set_width(val),
+            7 => self.reverb.set_frozen(val > 0.5),
+            _ => ()
+        }
+    }
+
+    fn outputs(&self) -> usize {
+        2
+    }
+
+    fn get_output(&self, i: usize) -> f64 {
+        match i {
+            1 => self.output.1,
+            _ => 0.0
+        }
+    }
+}
+
+/// Convert a length in samples at `rate` to the current sample rate
+pub(crate) fn scale_length(length: usize, rate: f64) -> usize {
+    (length as f64 * crate::SAMPLE_RATE / rate).round() as usize
+}
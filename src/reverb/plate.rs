@@ -0,0 +1,295 @@
+use crate::delay::{DelayLine, Interpolation};
+use crate::modules::Phase;
+use crate::util::clamp;
+use super::{Reverb, wet_gains, scale_length};
+
+const TWOPI: f64 = std::f64::consts::PI * 2.0;
+
+/// Sample rate of the delay lengths in Dattorro's paper
+const PLATE_RATE: f64 = 29761.0;
+
+const INPUT_DIFFUSION: [(usize, f64); 4] = [(142, 0.75), (107, 0.75), (379, 0.625), (277, 0.625)];
+const DECAY_DIFFUSION_1: f64 = 0.7;
+const DECAY_DIFFUSION_2: f64 = 0.5;
+const BANDWIDTH: f64 = 0.9995;
+const EXCURSION: f64 = 16.0;
+const MOD_RATE: f64 = 1.0;
+const OUTPUT_GAIN: f64 = 0.6;
+
+/// Schroeder allpass with a tappable delay line
+#[derive(Debug, Clone)]
+struct Diffuser {
+    delay: DelayLine,
+    length: f64,
+    gain: f64,
+}
+
+impl Diffuser {
+    fn new(length: usize, gain: f64, excursion: f64) -> Self {
+        let length = scale_length(length, PLATE_RATE);
+        Self {
+            delay: DelayLine::new(length + excursion as usize + 2, Interpolation::Linear),
+            length: length as f64,
+            gain,
+        }
+    }
+
+    /// `offset` is added to the delay length, for modulation
+    fn process(&mut self, input: f64, offset: f64) -> f64 {
+        let delayed = self.delay.read(self.length + offset);
+        let w = input + self.gain * delayed;
+        self.delay.write(w);
+        delayed - self.gain * w
+    }
+
+    fn tap(&self, delay: usize) -> f64 {
+        self.delay.tap(scale_length(delay, PLATE_RATE))
+    }
+}
+
+/// Delay line with taps given in samples at `PLATE_RATE`
+#[derive(Debug, Clone)]
+struct PlateDelay {
+    delay: DelayLine,
+    length: usize,
+}
+
+impl PlateDelay {
+    fn new(length: usize) -> Self {
+        let length = scale_length(length, PLATE_RATE);
+        Self { delay: DelayLine::new(length, Interpolation::Linear), length }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.delay.tap(self.length);
+        self.delay.write(input);
+        output
+    }
+
+    fn tap(&self, delay: usize) -> f64 {
+        self.delay.tap(scale_length(delay, PLATE_RATE))
+    }
+}
+
+/// One half of the figure-eight tank
+#[derive(Debug, Clone)]
+struct Tank {
+    diffuser_1: Diffuser,
+    delay_1: PlateDelay,
+    damping_state: f64,
+    diffuser_2: Diffuser,
+    delay_2: PlateDelay,
+}
+
+impl Tank {
+    fn new(lengths: [usize; 4]) -> Self {
+        Self {
+            diffuser_1: Diffuser::new(lengths[0], -DECAY_DIFFUSION_1, EXCURSION),
+            delay_1: PlateDelay::new(lengths[1]),
+            damping_state: 0.0,
+            diffuser_2: Diffuser::new(lengths[2], DECAY_DIFFUSION_2, 0.0),
+            delay_2: PlateDelay::new(lengths[3]),
+        }
+    }
+
+    fn process(&mut self, input: f64, modulation: f64, damping: f64, decay: f64) -> f64 {
+        let x = self.diffuser_1.process(input, modulation);
+        let x = self.delay_1.process(x);
+        self.damping_state = x * (1.0 - damping) + self.damping_state * damping;
+        let x = self.diffuser_2.process(self.damping_state * decay, 0.0);
+        self.delay_2.process(x) * decay
+    }
+
+    fn reset(&mut self) {
+        self.diffuser_1.delay.reset();
+        self.delay_1.delay.reset();
+        self.damping_state = 0.0;
+        self.diffuser_2.delay.reset();
+        self.delay_2.delay.reset();
+    }
+}
+
+/// Plate reverb after Jon Dattorro,
+/// "Effect Design Part 1: Reverberator and Other Filters" (1997).
+///
+/// The input is band limited and diffused by four allpasses
+/// and then fed into a figure-eight "tank" of two cross-coupled
+/// delay and allpass chains.
+/// The stereo output is built from taps spread across the tank.
+///
+/// Room size maps to the tank decay, dampening to the tank lowpass.
+pub struct Plate {
+    bandwidth_state: f64,
+    input_diffusers: Vec<Diffuser>,
+    left: Tank,
+    right: Tank,
+    lfo: Phase,
+    // Outputs of the tanks from the previous sample, for the cross-coupling
+    feedback: (f64, f64),
+    decay: f64,
+    damping: f64,
+    input_gain: f64,
+    wet_gains: (f64, f64),
+    wet: f64,
+    dry: f64,
+    width: f64,
+    dampening: f64,
+    room_size: f64,
+    frozen: bool,
+}
+
+impl Plate {
+    pub fn new() -> Self {
+        let input_diffusers = INPUT_DIFFUSION.iter()
+            .map(|(length, gain)| Diffuser::new(*length, *gain, 0.0))
+            .collect();
+
+        let mut plate = Self {
+            bandwidth_state: 0.0,
+            input_diffusers,
+            left: Tank::new([672, 4453, 1800, 3720]),
+            right: Tank::new([908, 4217, 2656, 3163]),
+            lfo: Phase::new(MOD_RATE),
+            feedback: (0.0, 0.0),
+            decay: 0.5,
+            damping: 0.0005,
+            input_gain: 1.0,
+            wet_gains: (0.0, 0.0),
+            wet: 0.0,
+            dry: 0.0,
+            width: 0.0,
+            dampening: 0.0,
+            room_size: 0.0,
+            frozen: false,
+        };
+
+        plate.set_wet(1.0);
+        plate.set_width(1.0);
+        plate.set_dampening(0.5);
+        plate.set_room_size(0.5);
+
+        plate
+    }
+
+    fn update_tank(&mut self) {
+        if self.frozen {
+            self.decay = 1.0;
+            self.damping = 0.0;
+            self.input_gain = 0.0;
+        } else {
+            self.decay = clamp(0.2 + self.room_size * 0.78, 0.0, 0.98);
+            self.damping = clamp(self.dampening * 0.8, 0.0, 0.8);
+            self.input_gain = 1.0;
+        }
+    }
+}
+
+impl Default for Plate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reverb for Plate {
+    fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        let input_mixed = (input.0 + input.1) * 0.5 * self.input_gain;
+
+        self.bandwidth_state = input_mixed * BANDWIDTH + self.bandwidth_state * (1.0 - BANDWIDTH);
+        let mut x = self.bandwidth_state;
+        for diffuser in self.input_diffusers.iter_mut() {
+            x = diffuser.process(x, 0.0);
+        }
+
+        let lfo = (self.lfo.get() * TWOPI).sin();
+        let left = self.left.process(
+            x + self.feedback.1, (lfo + 1.0) * 0.5 * EXCURSION, self.damping, self.decay
+        );
+        let right = self.right.process(
+            x + self.feedback.0, (1.0 - lfo) * 0.5 * EXCURSION, self.damping, self.decay
+        );
+        self.feedback = (left, right);
+
+        let (l, r) = (&self.left, &self.right);
+        let out_l = r.delay_1.tap(266)
+            + r.delay_1.tap(2974)
+            - r.diffuser_2.tap(1913)
+            + r.delay_2.tap(1996)
+            - l.delay_1.tap(1990)
+            - l.diffuser_2.tap(187)
+            - l.delay_2.tap(1066);
+        let out_r = l.delay_1.tap(353)
+            + l.delay_1.tap(3627)
+            - l.diffuser_2.tap(1228)
+            + l.delay_2.tap(2673)
+            - r.delay_1.tap(2111)
+            - r.diffuser_2.tap(335)
+            - r.delay_2.tap(121);
+        let out = (out_l * OUTPUT_GAIN, out_r * OUTPUT_GAIN);
+
+        (out.0 * self.wet_gains.0 + out.1 * self.wet_gains.1 + input.0 * self.dry,
+         out.1 * self.wet_gains.0 + out.0 * self.wet_gains.1 + input.1 * self.dry)
+    }
+
+    fn set_room_size(&mut self, value: f64) {
+        self.room_size = value;
+        self.update_tank();
+    }
+
+    fn room_size(&self) -> f64 {
+        self.room_size
+    }
+
+    fn set_dampening(&mut self, value: f64) {
+        self.dampening = value;
+        self.update_tank();
+    }
+
+    fn dampening(&self) -> f64 {
+        self.dampening
+    }
+
+    fn set_wet(&mut self, value: f64) {
+        self.wet = value;
+        self.wet_gains = wet_gains(self.wet, self.width);
+    }
+
+    fn wet(&self) -> f64 {
+        self.wet
+    }
+
+    fn set_dry(&mut self, value: f64) {
+        self.dry = value;
+    }
+
+    fn dry(&self) -> f64 {
+        self.dry
+    }
+
+    fn set_width(&mut self, value: f64) {
+        self.width = value;
+        self.wet_gains = wet_gains(self.wet, self.width);
+    }
+
+    fn width(&self) -> f64 {
+        self.width
+    }
+
+    fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+        self.update_tank();
+    }
+
+    fn frozen(&self) -> bool {
+        self.frozen
+    }
+
+    fn reset(&mut self) {
+        self.bandwidth_state = 0.0;
+        for diffuser in self.input_diffusers.iter_mut() {
+            diffuser.delay.reset();
+        }
+        self.left.reset();
+        self.right.reset();
+        self.feedback = (0.0, 0.0);
+    }
+}
@@ -0,0 +1,255 @@
+//! Scala scale (.scl) and keyboard mapping (.kbm) files,
+//! see http://www.huygens-fokker.org/scala/scl_format.html
+
+use std::fs;
+use std::path::Path;
+
+use crate::pitch::{KeyboardMapping, Tuning};
+
+/// Lines that are not comments, comments start with `!`
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().filter(|l| !l.starts_with('!'))
+}
+
+/// Pitch of a scale line in cents, values with a period are cents,
+/// all others are ratios like "3/2" or "2"
+fn parse_pitch(line: &str) -> Result<f64, String> {
+    let value = line.split_whitespace().next()
+        .ok_or_else(|| "Missing pitch in scale".to_string())?;
+    let invalid = || format!("Invalid pitch \"{}\" in scale", value);
+
+    if value.contains('.') {
+        return value.parse().map_err(|_| invalid());
+    }
+
+    let mut parts = value.splitn(2, '/');
+    let numerator: f64 = parts.next().unwrap().parse().map_err(|_| invalid())?;
+    let denominator: f64 = match parts.next() {
+        Some(d) => d.parse().map_err(|_| invalid())?,
+        None => 1.0,
+    };
+    if numerator <= 0.0 || denominator <= 0.0 {
+        return Err(invalid());
+    }
+
+    Ok(1200.0 * (numerator / denominator).log2())
+}
+
+/// Tuning from the contents of a .scl file
+pub fn parse_scl(text: &str) -> Result<Tuning, String> {
+    let mut lines = lines(text);
+
+    // The first line is the description and may be empty
+    lines.next().ok_or_else(|| "Empty scale file".to_string())?;
+    let count: usize = lines.next()
+        .and_then(|l| l.trim().parse().ok())
+        .ok_or_else(|| "Missing number of notes in scale".to_string())?;
+    if count == 0 {
+        return Err("Scale needs at least one note".to_string());
+    }
+
+    let steps = lines.filter(|l| !l.trim().is_empty())
+        .take(count)
+        .map(parse_pitch)
+        .collect::<Result<Vec<f64>, String>>()?;
+    if steps.len() != count {
+        return Err(format!("Scale has {} of {} notes", steps.len(), count));
+    }
+
+    Ok(Tuning::from_cents(steps))
+}
+
+/// Keyboard mapping from the contents of a .kbm file
+pub fn parse_kbm(text: &str) -> Result<KeyboardMapping, String> {
+    let mut lines = lines(text).map(str::trim).filter(|l| !l.is_empty());
+    let mut next = |name: &str| lines.next()
+        .and_then(|l| l.split_whitespace().next())
+        .map(str::to_string)
+        .ok_or_else(|| format!("Missing {} in keyboard mapping", name));
+    let invalid = |name: &str, value: &str| format!("Invalid {} \"{}\" in keyboard mapping", name, value);
+
+    let mut header = [0; 5];
+    for (value, name) in header.iter_mut()
+        .zip(&["size", "first note", "last note", "middle note", "reference note"]) {
+        let line = next(name)?;
+        *value = match line.parse::<usize>() {
+            Ok(v) if *name == "size" || v <= 127 => v,
+            _ => return Err(invalid(name, &line)),
+        };
+    }
+    let [size, first_note, last_note, middle_note, reference_note] = header;
+
+    let line = next("reference frequency")?;
+    let reference_freq: f64 = match line.parse() {
+        Ok(f) if f > 0.0 => f,
+        _ => return Err(invalid("reference frequency", &line)),
+    };
+    let line = next("octave degree")?;
+    let octave_degree: i32 = line.parse().map_err(|_| invalid("octave degree", &line))?;
+
+    // Missing keys at the end are not mapped
+    let mut keys = Vec::with_capacity(size);
+    for _ in 0..size {
+        let key = match next("key").ok() {
+            Some(ref x) if x == "x" || x == "X" => None,
+            Some(degree) => Some(degree.parse().map_err(|_| invalid("key", &degree))?),
+            None => None,
+        };
+        keys.push(key);
+    }
+    if size > 0 && keys.iter().all(Option::is_none) {
+        return Err("Keyboard mapping has no mapped keys".to_string());
+    }
+
+    Ok(KeyboardMapping {
+        keys,
+        first_note: first_note as u8,
+        last_note: last_note as u8,
+        middle_note: middle_note as u8,
+        reference_note: reference_note as u8,
+        reference_freq,
+        octave_degree,
+    })
+}
+
+/// Load a tuning from a .scl file and an optional .kbm file,
+/// without a keyboard mapping the scale starts on C4 with A4 = 440 Hz
+pub fn load<P: AsRef<Path>>(scl: P, kbm: Option<P>) -> Result<Tuning, String> {
+    let read = |path: &Path| fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e));
+
+    let tuning = parse_scl(&read(scl.as_ref())?)?;
+    match kbm {
+        Some(kbm) => Ok(tuning.with_mapping(parse_kbm(&read(kbm.as_ref())?)?)),
+        None => Ok(tuning),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::midi_to_freq;
+
+    const EQUAL_12: &str = "! 12tet.scl
+!
+12 tone equal temperament
+ 12
+!
+ 100.0
+ 200.
+ 300.0
+ 400.0
+ 500.0
+ 600.0
+ 700.0
+ 800.0
+ 900.0
+ 1000.0
+ 1100.0
+ 2/1
+";
+
+    const JUST_MAJOR: &str = "! just_major.scl
+Just intonation major scale
+7
+9/8
+5/4
+4/3 perfect fourth
+3/2 perfect fifth
+5/3
+15/8
+2
+";
+
+    /// Just major scale on the white keys, C4 = 264 Hz
+    const WHITE_KEYS: &str = "! white.kbm
+12
+0
+127
+60
+60
+264.0
+7
+! Mapping
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+x
+6
+";
+
+    fn assert_freq(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{} Hz instead of {} Hz", actual, expected);
+    }
+
+    #[test]
+    fn equal_temperament_matches_midi() {
+        let tuning = parse_scl(EQUAL_12).unwrap();
+        assert_eq!(tuning.len(), 12);
+        for note in 0..128 {
+            assert_freq(tuning.freq(note as f64), midi_to_freq(note as f64));
+        }
+    }
+
+    #[test]
+    fn just_scale_with_linear_mapping() {
+        let tuning = parse_scl(JUST_MAJOR).unwrap().with_reference(60, 264.0);
+        let expected = [264.0, 297.0, 330.0, 352.0, 396.0, 440.0, 495.0, 528.0, 594.0];
+        for (note, freq) in (60..).zip(expected.iter()) {
+            assert_freq(tuning.freq(note as f64), *freq);
+        }
+        assert_freq(tuning.freq(53.0), 132.0);
+    }
+
+    #[test]
+    fn just_scale_on_white_keys() {
+        let tuning = parse_scl(JUST_MAJOR).unwrap()
+            .with_mapping(parse_kbm(WHITE_KEYS).unwrap());
+
+        let white = [60, 62, 64, 65, 67, 69, 71, 72];
+        let expected = [264.0, 297.0, 330.0, 352.0, 396.0, 440.0, 495.0, 528.0];
+        for (note, freq) in white.iter().zip(expected.iter()) {
+            assert!(tuning.is_mapped(*note));
+            assert_freq(tuning.freq(*note as f64), *freq);
+        }
+        assert_freq(tuning.freq(57.0), 220.0);
+        assert!(!tuning.is_mapped(61));
+        assert_freq(tuning.note(330.0), 64.0);
+    }
+
+    #[test]
+    fn reference_pitch() {
+        let kbm = "12\n0\n127\n60\n69\n432.0\n12\n0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n";
+        let tuning = parse_scl(EQUAL_12).unwrap().with_mapping(parse_kbm(kbm).unwrap());
+        assert_freq(tuning.freq(69.0), 432.0);
+        assert_freq(tuning.freq(81.0), 864.0);
+        assert_freq(tuning.freq(60.0), 432.0 * 2.0_f64.powf(-9.0 / 12.0));
+    }
+
+    #[test]
+    fn equal_19() {
+        let scl = format!("19-EDO\n19\n{}",
+            (1..=19).map(|i| format!("{:.6}\n", i as f64 * 1200.0 / 19.0)).collect::<String>());
+        let tuning = parse_scl(&scl).unwrap().with_reference(60, 261.625565);
+        assert_freq(tuning.freq(79.0), 523.25113);
+        assert_freq(tuning.freq(61.0), 261.625565 * 2.0_f64.powf(1.0 / 19.0));
+        assert!((tuning.note(tuning.freq(66.25)) - 66.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!(parse_scl("").is_err());
+        assert!(parse_scl("Too short\n3\n100.0\n200.0\n").is_err());
+        assert!(parse_scl("Negative\n1\n-3/2\n").is_err());
+        assert!(parse_scl("Garbage\n1\nabc\n").is_err());
+        assert!(parse_kbm("12\n0\n127\n60\n69\n").is_err());
+        assert!(parse_kbm("1\n0\n127\n60\n69\n440.0\n1\nx\n").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use rand::{FromEntropy, Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::modules::Module;
+use crate::pitch::Tuning;
+use crate::trigger::Trigger;
+use crate::util::select;
+
+pub const MAX_STEPS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    /// Frequency in Hz, or MIDI note if the sequencer has a tuning
+    pub pitch: f64,
+    pub gate: bool,
+    pub velocity: f64,
+    /// Chance for the gate to fire, 0..1
+    pub probability: f64,
+}
+
+impl Step {
+    pub fn new(pitch: f64) -> Self {
+        Self {
+            pitch,
+            gate: true,
+            velocity: 1.0,
+            probability: 1.0,
+        }
+    }
+
+    /// A step without gate, holds the previous pitch
+    pub fn rest() -> Self {
+        Self {
+            gate: false,
+            ..Self::new(0.0)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Reverse,
+    /// Back and forth, without repeating the first and last step
+    PingPong,
+    Random,
+}
+
+impl Direction {
+    /// Select a direction with a control value 0..1
+    pub fn from_control(val: f64) -> Self {
+        match select(val, 4) {
+            0 => Direction::Forward,
+            1 => Direction::Reverse,
+            2 => Direction::PingPong,
+            _ => Direction::Random,
+        }
+    }
+}
+
+/// Step sequencer advancing on each clock trigger.
+///
+/// The gate output follows the clock,
+/// so the gate length is set by the clock's pulse width.
+///
+/// Inputs:
+///
+/// 0. Clock
+/// 1. Reset, the next clock plays the first step
+/// 2. Direction, control value 0..1
+/// 3. Length in steps
+///
+/// Outputs:
+///
+/// 0. Pitch in Hz
+/// 1. Gate
+/// 2. Velocity
+pub struct Sequencer {
+    steps: Vec<Step>,
+    length: usize,
+    direction: Direction,
+    // Current step, `None` after a reset
+    position: Option<usize>,
+    forward: bool,
+    clock: Trigger,
+    reset: Trigger,
+    // Pitch of the current step before tuning
+    note: f64,
+    pitch: f64,
+    tuning: Option<Tuning>,
+    velocity: f64,
+    // Whether the gate of the current step fired
+    active: bool,
+    rng: StdRng,
+}
+
+impl Sequencer {
+    /// Sequencer with `length` steps of 0 Hz and no gate
+    pub fn new(length: usize) -> Self {
+        assert!((1..=MAX_STEPS).contains(&length), "Sequencer needs 1 to 64 steps");
+        Self {
+            steps: vec![Step::rest(); MAX_STEPS],
+            length,
+            direction: Direction::Forward,
+            position: None,
+            forward: true,
+            clock: Trigger::new(),
+            reset: Trigger::new(),
+            note: 0.0,
+            pitch: 0.0,
+            tuning: None,
+            velocity: 0.0,
+            active: false,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Sequencer playing `steps` in order
+    pub fn from_steps(steps: &[Step]) -> Self {
+        let mut sequencer = Self::new(steps.len());
+        sequencer.steps[..steps.len()].copy_from_slice(steps);
+        sequencer
+    }
+
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.set_seed(seed);
+        self
+    }
+
+    /// Step pitches are MIDI notes played in `tuning`
+    pub fn with_tuning(mut self, tuning: Tuning) -> Self {
+        self.tuning = Some(tuning);
+        self
+    }
+
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
+
+    /// Number of steps played, 1..64
+    pub fn set_length(&mut self, length: usize) {
+        self.length = length.clamp(1, MAX_STEPS);
+        self.position = self.position.map(|p| p.min(self.length - 1));
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn set_step(&mut self, i: usize, step: Step) {
+        self.steps[i] = step;
+    }
+
+    pub fn step_mut(&mut self, i: usize) -> &mut Step {
+        &mut self.steps[i]
+    }
+
+    /// Index of the current step
+    pub fn position(&self) -> Option<usize> {
+        self.position
+    }
+
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.forward = true;
+    }
+
+    fn next_position(&mut self) -> usize {
+        let last = self.length - 1;
+        let position = match self.position {
+            None => return match self.direction {
+                Direction::Reverse => last,
+                Direction::Random => self.rng.gen_range(0, self.length),
+                _ => 0,
+            },
+            Some(position) => position,
+        };
+
+        match self.direction {
+            Direction::Forward => if position >= last { 0 } else { position + 1 },
+            Direction::Reverse => if position == 0 { last } else { position - 1 },
+            Direction::PingPong if last == 0 => 0,
+            Direction::PingPong => {
+                if self.forward && position >= last {
+                    self.forward = false;
+                } else if !self.forward && position == 0 {
+                    self.forward = true;
+                }
+                if self.forward { position + 1 } else { position - 1 }
+            }
+            Direction::Random => self.rng.gen_range(0, self.length),
+        }
+    }
+
+    fn freq(&self, pitch: f64) -> f64 {
+        match &self.tuning {
+            Some(tuning) => tuning.freq(pitch),
+            None => pitch,
+        }
+    }
+
+    fn advance(&mut self) {
+        let position = self.next_position();
+        self.position = Some(position);
+
+        let step = self.steps[position];
+        self.active = step.gate
+            && (step.probability >= 1.0 || self.rng.gen::<f64>() < step.probability);
+        if self.active {
+            self.note = step.pitch;
+            self.pitch = self.freq(step.pitch);
+            self.velocity = step.velocity;
+        }
+    }
+}
+
+impl Module for Sequencer {
+    fn get(&mut self) -> f64 {
+        self.pitch
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 if self.clock.process(val) => self.advance(),
+            1 if self.reset.process(val) => self.reset(),
+            2 => self.set_direction(Direction::from_control(val)),
+            3 => self.set_length(val.round() as usize),
+            _ => ()
+        }
+    }
+
+    fn outputs(&self) -> usize {
+        3
+    }
+
+    fn get_output(&self, i: usize) -> f64 {
+        match i {
+            0 => self.pitch,
+            1 if self.active && self.clock.is_high() => 1.0,
+            2 => self.velocity,
+            _ => 0.0
+        }
+    }
+
+    /// Only used if the step pitches are MIDI notes, see `with_tuning`
+    fn set_tuning(&mut self, tuning: &Tuning) {
+        if self.tuning.is_some() {
+            self.tuning = Some(tuning.clone());
+            self.pitch = self.freq(self.note);
+        }
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+}
@@ -1,71 +1,50 @@
-use rand::{Rng, thread_rng};
+use rand::{FromEntropy, Rng, SeedableRng};
+use rand::rngs::StdRng;
 
-use crate::SAMPLE_RATE;
+use crate::delay::{DelayLine, FractionalAllpass, Interpolation};
+use crate::karplus_strong::{Excitation, loop_delay, pick};
 use super::Source;
 
 pub struct KarplusStrong {
-    wavetable: Vec<f64>,
-    index: usize,
-    size: usize,
+    line: DelayLine,
+    period: usize,
+    tuning: FractionalAllpass,
 }
 
 impl KarplusStrong {
     pub fn new(freq: f64) -> Self {
-        let p = (SAMPLE_RATE / freq + 0.5) as usize;
-
-        let mut wavetable = Vec::with_capacity(p);
-        let mut rng = thread_rng();
-        for _i in 0..p {
-            wavetable.push(rng.gen_range(-1.0, 1.0));
-            // if rng.gen_range(0.0, 1.0) > 0.5 {
-            //     wavetable.push(1.0);
-            // } else {
-            //     wavetable.push(-1.0);
-            // }
-            // if ((i * 4) % p) > (p / 2) {
-            //     wavetable.push(rng.gen_range(-1.0, -0.5));
-            // } else {
-            //     wavetable.push(rng.gen_range(0.5, 1.0));
-            // }
-            // wavetable.push((((i as f64) / p as f64) % 1.0) * 2.0 - 1.0);
-            // wavetable.push(rng.gen_range(-1.0, 1.0));
-        }
-
-
-        Self { wavetable, size: p, index: 0 }
+        Self::with_excitation(freq, &Excitation::Noise, 0.0, &mut StdRng::from_entropy())
     }
 
-    fn prev(&self) -> f64 {
-        if self.index == 0 {
-            self.wavetable[self.size - 1]
-        } else {
-            self.wavetable[self.index - 1]
-        }
+    /// String with noise from a generator seeded with `seed`
+    pub fn with_seed(freq: f64, seed: u64) -> Self {
+        Self::with_excitation(freq, &Excitation::Noise, 0.0, &mut StdRng::seed_from_u64(seed))
     }
 
-    fn current(&self) -> f64 {
-        self.wavetable[self.index]
-    }
+    /// String plucked with `excitation` at `pick_position`,
+    /// see `karplus_strong::pick`
+    pub fn with_excitation<R: Rng>(freq: f64, excitation: &Excitation, pick_position: f64, rng: &mut R) -> Self {
+        let (period, fraction) = loop_delay(freq, 0.5);
+
+        let mut samples = vec![0.0; period + 1];
+        excitation.fill(&mut samples, rng);
+        pick(&mut samples, pick_position);
 
-    fn write(&mut self, val: f64) {
-        if self.index == 0 {
-            self.wavetable[self.size - 1] = val;
-        } else {
-            self.wavetable[self.index - 1] = val;
+        let mut line = DelayLine::new(period + 1, Interpolation::Linear);
+        for v in samples {
+            line.write(v);
         }
+
+        Self { line, period, tuning: FractionalAllpass::new(fraction) }
     }
 }
 
 impl Source for KarplusStrong {
     fn get(&mut self) -> f64 {
-        let avg = (self.current() + self.prev()) * 0.5;
-        self.write(avg);
-
-        self.index += 1;
-        if self.index == self.size {
-            self.index = 0;
-        }
+        let avg = (self.line.tap(self.period) + self.line.tap(self.period + 1)) * 0.5;
+        let out = self.tuning.process(avg);
+        self.line.write(out);
 
-        avg
+        out
     }
 }
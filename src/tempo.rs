@@ -0,0 +1,73 @@
+use crate::util::select;
+
+/// Length of a note, measured in beats (quarter notes)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Division {
+    beats: f64,
+}
+
+/// Divisions selectable through a control input,
+/// ordered from longest to shortest
+pub const DIVISIONS: [Division; 16] = [
+    Division { beats: 4.0 },          // 1/1
+    Division { beats: 3.0 },          // 1/2 dotted
+    Division { beats: 2.0 },          // 1/2
+    Division { beats: 1.5 },          // 1/4 dotted
+    Division { beats: 4.0 / 3.0 },    // 1/2 triplet
+    Division { beats: 1.0 },          // 1/4
+    Division { beats: 0.75 },         // 1/8 dotted
+    Division { beats: 2.0 / 3.0 },    // 1/4 triplet
+    Division { beats: 0.5 },          // 1/8
+    Division { beats: 0.375 },        // 1/16 dotted
+    Division { beats: 1.0 / 3.0 },    // 1/8 triplet
+    Division { beats: 0.25 },         // 1/16
+    Division { beats: 0.1875 },       // 1/32 dotted
+    Division { beats: 1.0 / 6.0 },    // 1/16 triplet
+    Division { beats: 0.125 },        // 1/32
+    Division { beats: 1.0 / 12.0 },   // 1/32 triplet
+];
+
+impl Division {
+    /// A `numerator / denominator` of a whole note,
+    /// e.g. `Division::new(1, 4)` is one beat
+    pub fn new(numerator: u32, denominator: u32) -> Self {
+        Self { beats: 4.0 * f64::from(numerator) / f64::from(denominator) }
+    }
+
+    pub fn dotted(self) -> Self {
+        Self { beats: self.beats * 1.5 }
+    }
+
+    pub fn triplet(self) -> Self {
+        Self { beats: self.beats * 2.0 / 3.0 }
+    }
+
+    /// Pick one of `DIVISIONS` with a control value in 0..1
+    pub fn from_control(val: f64) -> Self {
+        DIVISIONS[select(val, DIVISIONS.len())]
+    }
+
+    pub fn beats(&self) -> f64 {
+        self.beats
+    }
+
+    /// Length in seconds at `bpm` beats per minute
+    pub fn seconds(&self, bpm: f64) -> f64 {
+        self.beats * 60.0 / bpm.max(1.0)
+    }
+
+    /// Frequency in Hz of one cycle per division at `bpm`
+    pub fn freq(&self, bpm: f64) -> f64 {
+        1.0 / self.seconds(bpm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn divisions_longest_first() {
+        assert!(DIVISIONS.windows(2).all(|w| w[0].beats() >= w[1].beats()));
+    }
+}
@@ -0,0 +1,119 @@
+//! Trigger and gate conventions shared by all modules.
+//!
+//! Triggers and gates are plain signals.
+//! A value above `THRESHOLD` counts as high, modules react to the rising edge.
+//! Trigger outputs are pulses of 1.0 lasting `PULSE_LENGTH` seconds,
+//! gate outputs stay at 1.0 for as long as the gate is open.
+
+use crate::SAMPLE_RATE;
+use crate::modules::Module;
+
+pub const THRESHOLD: f64 = 0.5;
+
+/// Length of trigger pulses in seconds
+pub const PULSE_LENGTH: f64 = 0.001;
+
+/// Detects rising edges of a trigger or gate signal
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    high: bool,
+}
+
+impl Trigger {
+    pub fn new() -> Self {
+        Self { high: false }
+    }
+
+    /// Returns `true` if the signal just went high
+    pub fn process(&mut self, val: f64) -> bool {
+        let was_high = self.high;
+        self.high = val > THRESHOLD;
+        self.high && !was_high
+    }
+
+    pub fn is_high(&self) -> bool {
+        self.high
+    }
+}
+
+impl Default for Trigger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generates trigger pulses of `PULSE_LENGTH`
+#[derive(Debug, Clone)]
+pub struct Pulse {
+    remaining: usize,
+}
+
+impl Pulse {
+    pub fn new() -> Self {
+        Self { remaining: 0 }
+    }
+
+    /// Start a new pulse
+    pub fn trigger(&mut self) {
+        self.remaining = ((PULSE_LENGTH * SAMPLE_RATE) as usize).max(1);
+    }
+
+    /// Output for the current sample, 1.0 while the pulse lasts
+    pub fn process(&mut self) -> f64 {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for Pulse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Samples the signal on each trigger and holds it until the next one
+///
+/// Inputs:
+///
+/// 0. Signal
+/// 1. Trigger
+#[derive(Debug, Clone)]
+pub struct SampleAndHold {
+    input: f64,
+    value: f64,
+    trigger: Trigger,
+}
+
+impl SampleAndHold {
+    pub fn new() -> Self {
+        Self {
+            input: 0.0,
+            value: 0.0,
+            trigger: Trigger::new(),
+        }
+    }
+}
+
+impl Default for SampleAndHold {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Module for SampleAndHold {
+    fn get(&mut self) -> f64 {
+        self.value
+    }
+
+    fn set_input(&mut self, i: usize, val: f64) {
+        match i {
+            0 => self.input = val,
+            1 if self.trigger.process(val) => self.value = self.input,
+            _ => ()
+        }
+    }
+}
@@ -17,3 +17,17 @@ pub fn clamp_audio(i: f64) -> f64 {
         i
     }
 }
+
+pub fn db_to_linear(db: f64) -> f64 {
+    10.0_f64.powf(db / 20.0)
+}
+
+/// Convert a linear gain to dB, silence maps to -inf
+pub fn linear_to_db(gain: f64) -> f64 {
+    20.0 * gain.log10()
+}
+
+/// Index of one of `n` equal ranges of a control value in 0..1
+pub fn select(val: f64, n: usize) -> usize {
+    ((clamp(val, 0.0, 1.0) * n as f64) as usize).min(n.max(1) - 1)
+}
@@ -0,0 +1,92 @@
+//! Golden output tests, see `patchwork::golden`.
+//! Run with `PATCHWORK_BLESS=1` to update the references after an intended change.
+
+use patchwork::clock::Clock;
+use patchwork::freeverb::Freeverb;
+use patchwork::golden::{Golden, render, render_rack};
+use patchwork::karplus_strong::KarplusStrong;
+use patchwork::lfo::{Lfo, LfoShape};
+use patchwork::modules::Sine;
+use patchwork::pitch::Tuning;
+use patchwork::rack::Rack;
+use patchwork::reverb::ReverbModule;
+use patchwork::sequencer::{Sequencer, Step};
+
+fn check(result: Result<(), String>) {
+    if let Err(e) = result {
+        panic!("{}", e);
+    }
+}
+
+#[test]
+fn sine() {
+    let samples = render(&mut Sine::new(440.0), 4800, |_, _| ());
+    check(Golden::new("sine").check_wav(&samples).map(|_| ()));
+}
+
+#[test]
+fn lfo_sample_and_hold() {
+    let mut lfo = Lfo::new(LfoShape::SampleAndHold, 100.0).with_seed(3);
+    let samples = render(&mut lfo, 4800, |_, _| ());
+    check(Golden::new("lfo_sample_and_hold").check_wav(&samples).map(|_| ()));
+}
+
+#[test]
+fn freeverb_impulse() {
+    let mut reverb = ReverbModule::new(Freeverb::new());
+    let samples = render(&mut reverb, 9600, |m, t| {
+        let impulse = if t == 0 { 1.0 } else { 0.0 };
+        m.set_input(0, impulse);
+        m.set_input(1, impulse);
+    });
+    check(Golden::new("freeverb_impulse").check_wav(&samples).map(|_| ()));
+}
+
+#[test]
+fn karplus_strong() {
+    let mut string = KarplusStrong::new(220.0, 1.0, 1.0).with_seed(7);
+    let samples = render(&mut string, 9600, |_, _| ());
+    check(Golden::new("karplus_strong").check_wav(&samples).map(|_| ()));
+}
+
+/// Sequenced string through a reverb, with randomness from the rack seed
+fn sequenced_string(seed: u64) -> Rack {
+    let mut rack = Rack::new(1).with_seed(seed);
+
+    let clock = rack.register_module(Box::new(Clock::new(480.0)));
+    let steps = [Step::new(57.0), Step::new(60.0), Step::new(64.0), Step::new(67.0)];
+    let sequencer = rack.register_module(Box::new(
+        Sequencer::from_steps(&steps).with_tuning(Tuning::standard())
+    ));
+    rack.patch(clock, (sequencer, 0));
+
+    let brightness = rack.register_module(Box::new(Lfo::new(LfoShape::SampleAndHold, 8.0)));
+    let string = rack.register_module(Box::new(KarplusStrong::new(220.0, 1.0, 1.0)));
+    rack.patch(sequencer, (string, 0));
+    rack.patch(sequencer + 1, (string, 1));
+    rack.patch(brightness, (string, 6));
+
+    let reverb = rack.register_module(Box::new(ReverbModule::new(Freeverb::new())));
+    rack.patch(string, (reverb, 0));
+    rack.patch(string, (reverb, 1));
+    rack.patch(0, (reverb, 4));
+    rack.set_output(reverb, reverb + 1);
+    rack
+}
+
+#[test]
+fn rack() {
+    let samples = render_rack(&mut sequenced_string(1), 12000, |rack, t| {
+        if t == 0 {
+            rack.process_control(0, 40);
+        }
+    });
+    check(Golden::new("sequenced_string").stereo().check_wav(&samples).map(|_| ()));
+}
+
+#[test]
+fn rack_seed() {
+    let render = |seed| render_rack(&mut sequenced_string(seed), 4800, |_, _| ());
+    assert_eq!(render(2), render(2));
+    assert_ne!(render(2), render(3));
+}